@@ -0,0 +1,197 @@
+use cosmwasm_std::{
+    callable_points, entry_point,
+    Addr, Attribute, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128
+};
+use cw2::set_contract_version;
+use cw20_base::contract::{execute_burn, execute_mint, query_minter};
+use cw20_base::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+use crate::error::ContractError;
+
+// Version info for migration
+const CONTRACT_NAME: &str = "crates.io:cw20-base-dynamiclink";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// The token is a plain cw20, so shares can be transferred and used in pools.
+// Minting and burning for the staking contract go through the callable points.
+#[entry_point]
+pub fn instantiate(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let res = cw20_base::contract::instantiate(deps.branch(), env, info, msg)?;
+    // Explicitly set contract name and version, otherwise set to cw20-base info
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)
+        .map_err(ContractError::Std)?;
+
+    Ok(res)
+}
+
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    Ok(cw20_base::contract::execute(deps, env, info, msg)?)
+}
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    cw20_base::contract::query(deps, env, msg)
+}
+
+// the caller acts as the sender, so cw20-base only lets the minter mint
+fn mint_as(deps: DepsMut, env: Env, caller: Addr, recipient: String, amount: Uint128) -> StdResult<Vec<Attribute>> {
+    let info = MessageInfo {
+        sender: caller,
+        funds: vec![],
+    };
+    let res = execute_mint(deps, env, info, recipient, amount)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(res.attributes)
+}
+
+// burns from owner without an allowance, so only the minter may call it
+fn burn_from_as(deps: DepsMut, env: Env, caller: Addr, owner: String, amount: Uint128) -> StdResult<Vec<Attribute>> {
+    let minter = query_minter(deps.as_ref())?.map(|minter| minter.minter);
+    if minter.as_deref() != Some(caller.as_str()) {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&owner)?,
+        funds: vec![],
+    };
+    let res = execute_burn(deps, env, info, amount)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(res.attributes)
+}
+
+#[callable_points]
+mod callable_points {
+    use cosmwasm_std::to_binary;
+    use cw20_base::contract::{query_balance, query_token_info};
+    use super::*;
+
+    #[callable_point]
+    fn mint(deps: DepsMut, env: Env, recipient: String, amount: Uint128) -> StdResult<Vec<Attribute>> {
+        let caller : Addr = deps.api.get_caller_addr()?;
+        mint_as(deps, env, caller, recipient, amount)
+    }
+
+    #[callable_point]
+    fn burn_from(deps: DepsMut, env: Env, owner: String, amount: Uint128) -> StdResult<Vec<Attribute>> {
+        let caller : Addr = deps.api.get_caller_addr()?;
+        burn_from_as(deps, env, caller, owner, amount)
+    }
+
+    #[callable_point]
+    fn balance(deps: Deps, _env: Env, address: String) -> StdResult<Binary> {
+        to_binary(&query_balance(deps, address)?)
+    }
+
+    #[callable_point]
+    fn token_info(deps: Deps, _env: Env) -> StdResult<Binary> {
+        to_binary(&query_token_info(deps)?)
+    }
+
+    #[callable_point]
+    fn minter(deps: Deps, _env: Env) -> StdResult<Binary> {
+        to_binary(&query_minter(deps)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{from_binary, Addr, Uint128};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cw20::{BalanceResponse, MinterResponse, TokenInfoResponse};
+    use cw20_base::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+    use crate::contract::{burn_from_as, execute, instantiate, mint_as, query};
+
+    const MINTER: &str = "dex";
+    const HOLDER: &str = "seoul";
+
+    fn instantiate_msg() -> InstantiateMsg {
+        InstantiateMsg {
+            name: "Staked Cony".to_string(),
+            symbol: "SCONY".to_string(),
+            decimals: 6,
+            initial_balances: vec![],
+            mint: Some(MinterResponse {
+                minter: MINTER.to_string(),
+                cap: None,
+            }),
+            marketing: None,
+        }
+    }
+
+    #[test]
+    fn minting() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        let mint = ExecuteMsg::Mint {
+            recipient: HOLDER.to_string(),
+            amount: Uint128::new(100),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), mint.clone()).unwrap();
+
+        // only the minter can mint
+        execute(deps.as_mut(), mock_env(), mock_info(HOLDER, &[]), mint).unwrap_err();
+
+        let balance : BalanceResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::Balance { address: HOLDER.to_string() }).unwrap()
+        ).unwrap();
+        assert_eq!(Uint128::new(100), balance.balance);
+
+        let token_info : TokenInfoResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()
+        ).unwrap();
+        assert_eq!(Uint128::new(100), token_info.total_supply);
+    }
+
+    #[test]
+    fn callable_mint() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+
+        mint_as(deps.as_mut(), mock_env(), Addr::unchecked(MINTER), HOLDER.to_string(), Uint128::new(100)).unwrap();
+
+        // only the minter can mint
+        mint_as(deps.as_mut(), mock_env(), Addr::unchecked(HOLDER), HOLDER.to_string(), Uint128::new(100)).unwrap_err();
+
+        let token_info : TokenInfoResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()
+        ).unwrap();
+        assert_eq!(Uint128::new(100), token_info.total_supply);
+    }
+
+    #[test]
+    fn callable_burn_from() {
+        let mut deps = mock_dependencies();
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg()).unwrap();
+        mint_as(deps.as_mut(), mock_env(), Addr::unchecked(MINTER), HOLDER.to_string(), Uint128::new(100)).unwrap();
+
+        // only the minter can burn, even the holder can't
+        burn_from_as(deps.as_mut(), mock_env(), Addr::unchecked(HOLDER), HOLDER.to_string(), Uint128::new(40)).unwrap_err();
+        burn_from_as(deps.as_mut(), mock_env(), Addr::unchecked("stranger"), HOLDER.to_string(), Uint128::new(40)).unwrap_err();
+
+        burn_from_as(deps.as_mut(), mock_env(), Addr::unchecked(MINTER), HOLDER.to_string(), Uint128::new(40)).unwrap();
+
+        let balance : BalanceResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::Balance { address: HOLDER.to_string() }).unwrap()
+        ).unwrap();
+        assert_eq!(Uint128::new(60), balance.balance);
+
+        let token_info : TokenInfoResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()
+        ).unwrap();
+        assert_eq!(Uint128::new(60), token_info.total_supply);
+    }
+}
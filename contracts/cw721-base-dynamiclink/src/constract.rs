@@ -1,13 +1,15 @@
 use cosmwasm_std::{
     callable_points, dynamic_link, entry_point,
-    Addr, Contract, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Empty
+    Addr, Attribute, Contract, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Empty
 };
-use cw2::set_contract_version;
-use cw721_base::{Extension, InstantiateMsg};
+use cw2::{get_contract_version, set_contract_version};
+use cw721::Cw721Execute;
+use cw721_base::{Extension, InstantiateMsg, MintMsg};
+use semver::Version;
 use serde::{Deserialize, Serialize};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, MintingMsg};
+use crate::msg::{ExecuteMsg, MigrateMsg, MintingMsg};
 
 pub type Cw721BaseDynamicLinkContract<'a> =
 cw721_base::Cw721Contract<'a, Extension, Empty, Empty, Empty>;
@@ -45,6 +47,31 @@ pub fn execute(
     Ok(Response::default())
 }
 
+// the nft state is cw721-base's, so there is nothing to migrate yet besides
+// the version
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration(format!(
+            "cannot migrate {} to {}",
+            stored.contract, CONTRACT_NAME,
+        )));
+    }
+    if Version::parse(&stored.version)? > Version::parse(CONTRACT_VERSION)? {
+        return Err(ContractError::InvalidMigration(format!(
+            "cannot downgrade from {} to {}",
+            stored.version, CONTRACT_VERSION,
+        )));
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ExampleStruct {
     pub str_field: String,
@@ -71,10 +98,78 @@ trait Child: Contract {
     fn caller_address(&self) -> Addr;
 }
 
+#[derive(Contract)]
+struct Minter {
+    address: Addr,
+}
+
+// the staking contract keeps the owner of each position nft
+#[dynamic_link(Minter)]
+trait PositionOwners: Contract {
+    fn nft_transferred(&self, token_id: String, recipient: String) -> StdResult<Vec<Attribute>>;
+}
+
+// spender may send the token: it owns it, holds an approval for it or is an
+// operator of the owner, expired approvals don't count
+fn is_approved_for(deps: Deps, env: &Env, token_id: &str, spender: &str) -> StdResult<bool> {
+    let contract = Cw721BaseDynamicLinkContract::default();
+    let token = contract.tokens.load(deps.storage, token_id)?;
+    if token.owner == spender {
+        return Ok(true);
+    }
+    let approved = token
+        .approvals
+        .iter()
+        .any(|approval| approval.spender == spender && !approval.is_expired(&env.block));
+    if approved {
+        return Ok(true);
+    }
+
+    let spender = deps.api.addr_validate(spender)?;
+    let operator = contract.operators.may_load(deps.storage, (&token.owner, &spender))?;
+    Ok(operator.map_or(false, |expires| !expires.is_expired(&env.block)))
+}
+
+fn mint_as(deps: DepsMut, env: Env, caller: Addr, msg: MintingMsg) -> StdResult<Vec<Attribute>> {
+    let info = MessageInfo { sender: caller, funds: vec![] };
+    let res = Cw721BaseDynamicLinkContract::default()
+        .mint(deps, env, info, MintMsg {
+            token_id: msg.token_id,
+            owner: msg.owner,
+            token_uri: Some(msg.token_uri),
+            extension: Extension::from(Empty::default()),
+        })
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(res.attributes)
+}
+
+// only the minter may transfer on behalf of info.sender, any other caller
+// transfers as itself
+fn transfer_nft_as(
+    deps: DepsMut,
+    env: Env,
+    caller: Addr,
+    info: MessageInfo,
+    recipient: String,
+    token_id: String,
+) -> StdResult<Vec<Attribute>> {
+    let contract = Cw721BaseDynamicLinkContract::default();
+    let minter = contract.minter(deps.as_ref())?.minter;
+    let info = if caller == minter {
+        info
+    } else {
+        MessageInfo { sender: caller, funds: vec![] }
+    };
+    let res = contract
+        .transfer_nft(deps, env, info, recipient, token_id)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(res.attributes)
+}
+
 #[callable_points]
 mod callable_points {
-    use cosmwasm_std::{Attribute, Binary, Empty};
-    use cw721_base::{Extension, MintMsg, QueryMsg};
+    use cosmwasm_std::{Binary, Empty};
+    use cw721_base::{Extension, QueryMsg};
     use super::*;
 
     pub type Cw721BaseDynamicLinkContract<'a> =
@@ -95,30 +190,27 @@ mod callable_points {
         callee.caller_address()
     }
 
+    // only the minter mints, so nobody can take the position ids it hands out
     #[callable_point]
-    fn mint (deps: DepsMut, env: Env, msg: MintingMsg) -> Vec<Attribute> {
-        let minter = Cw721BaseDynamicLinkContract::default()
-            .minter(deps.as_ref());
-        let info = MessageInfo {
-            sender: deps.api.addr_validate(&minter.unwrap().minter).unwrap(),
-            funds: vec![],
-        };
-
-        let res = Cw721BaseDynamicLinkContract::default()
-            .mint(deps, env, info, MintMsg {
-                token_id: msg.token_id.clone(),
-                owner: msg.owner.clone(),
-                token_uri: Some(msg.token_uri.clone()),
-                extension: Extension::from(Empty::default()),
-            });
-        res.unwrap().attributes
+    fn mint(deps: DepsMut, env: Env, msg: MintingMsg) -> StdResult<Vec<Attribute>> {
+        let caller = deps.api.get_caller_addr()?;
+        mint_as(deps, env, caller, msg)
     }
 
+    // transfers the minter didn't make are reported back to it
     #[callable_point]
-    fn transfer_nft(deps: DepsMut, env: Env, info: MessageInfo, recipient: String, token_id: String) -> Vec<Attribute> {
-        let res = Cw721BaseDynamicLinkContract::default()
-            .transfer_nft(deps, env, info, recipient, token_id);
-        res.unwrap().attributes
+    fn transfer_nft(deps: DepsMut, env: Env, info: MessageInfo, recipient: String, token_id: String) -> StdResult<Vec<Attribute>> {
+        let caller = deps.api.get_caller_addr()?;
+        let minter = Cw721BaseDynamicLinkContract::default()
+            .minter(deps.as_ref())?
+            .minter;
+        let mut attributes = transfer_nft_as(deps, env, caller.clone(), info, recipient.clone(), token_id.clone())?;
+
+        if caller != minter {
+            let minter = Minter { address: Addr::unchecked(minter) };
+            attributes.extend(minter.nft_transferred(token_id, recipient)?);
+        }
+        Ok(attributes)
     }
 
     #[callable_point]
@@ -140,6 +232,11 @@ mod callable_points {
         Cw721BaseDynamicLinkContract::default().query(deps, env, query_msg)
     }
 
+    #[callable_point]
+    fn is_approved(deps: Deps, env: Env, token_id: String, spender: String) -> StdResult<bool> {
+        is_approved_for(deps, &env, &token_id, &spender)
+    }
+
     #[callable_point]
     fn owner_of(deps: Deps, env: Env, token_id: String, include_expired: bool, ) -> StdResult<Binary> {
         let query_msg = QueryMsg::OwnerOf {
@@ -157,7 +254,10 @@ mod tests {
     use cw721::Cw721Query;
     use cw721_base::{Cw721Contract, ExecuteMsg, Extension, InstantiateMsg, MintMsg};
     use cw721_base::entry::execute;
-    use crate::constract::instantiate;
+    use cw2::{get_contract_version, set_contract_version};
+    use crate::constract::{instantiate, is_approved_for, migrate, mint_as, transfer_nft_as};
+    use crate::msg::MintingMsg;
+    use crate::msg::MigrateMsg;
 
     const MINTER: &str = "merlin";
     const CONTRACT_NAME: &str = "Magic Power";
@@ -255,4 +355,102 @@ mod tests {
                 .add_attribute("token_id", token_id)
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn approvals() {
+        let mut deps = mock_dependencies();
+        let contract = setup_contract(deps.as_mut());
+
+        let token_id = "cw721_1".to_string();
+        let mint = ExecuteMsg::Mint {
+            0: MintMsg {
+                token_id: token_id.clone(),
+                owner: FROM_ADDR.to_string(),
+                token_uri: None,
+                extension: None,
+            },
+        };
+        contract.execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), mint).unwrap();
+        let approve = ExecuteMsg::Approve {
+            spender: "spender".to_string(),
+            token_id: token_id.clone(),
+            expires: None,
+        };
+        contract.execute(deps.as_mut(), mock_env(), mock_info(FROM_ADDR, &[]), approve).unwrap();
+        let approve_all = ExecuteMsg::ApproveAll {
+            operator: "operator".to_string(),
+            expires: None,
+        };
+        contract.execute(deps.as_mut(), mock_env(), mock_info(FROM_ADDR, &[]), approve_all).unwrap();
+
+        assert!(is_approved_for(deps.as_ref(), &mock_env(), &token_id, FROM_ADDR).unwrap());
+        assert!(is_approved_for(deps.as_ref(), &mock_env(), &token_id, "spender").unwrap());
+        assert!(is_approved_for(deps.as_ref(), &mock_env(), &token_id, "operator").unwrap());
+        assert!(!is_approved_for(deps.as_ref(), &mock_env(), &token_id, TO_ADDR).unwrap());
+        is_approved_for(deps.as_ref(), &mock_env(), "cw721_2", FROM_ADDR).unwrap_err();
+    }
+
+    #[test]
+    fn only_minter_mints() {
+        let mut deps = mock_dependencies();
+        let contract = setup_contract(deps.as_mut());
+        let msg = MintingMsg {
+            token_id: "cw721_1".to_string(),
+            owner: FROM_ADDR.to_string(),
+            token_uri: "https://www.finschia.network/".to_string(),
+        };
+
+        // nobody else can take an id the minter is about to hand out
+        mint_as(deps.as_mut(), mock_env(), Addr::unchecked("thief"), msg.clone()).unwrap_err();
+        assert_eq!(0, contract.token_count(deps.as_ref().storage).unwrap());
+
+        mint_as(deps.as_mut(), mock_env(), Addr::unchecked(MINTER), msg).unwrap();
+        assert_eq!(FROM_ADDR, contract.owner_of(deps.as_ref(), mock_env(), "cw721_1".to_string(), false).unwrap().owner);
+    }
+
+    #[test]
+    fn forged_transfer() {
+        let mut deps = mock_dependencies();
+        let contract = setup_contract(deps.as_mut());
+
+        let token_id = "cw721_1".to_string();
+        let mint = ExecuteMsg::Mint {
+            0: MintMsg {
+                token_id: token_id.clone(),
+                owner: FROM_ADDR.to_string(),
+                token_uri: None,
+                extension: None,
+            },
+        };
+        contract.execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), mint).unwrap();
+
+        // another contract can't pass the owner off as the sender
+        let forged = mock_info(FROM_ADDR, &[]);
+        transfer_nft_as(deps.as_mut(), mock_env(), Addr::unchecked("thief"), forged.clone(), "thief".into(), token_id.clone())
+            .unwrap_err();
+        assert_eq!(FROM_ADDR, contract.owner_of(deps.as_ref(), mock_env(), token_id.clone(), false).unwrap().owner);
+
+        // the minter acts for the owner
+        transfer_nft_as(deps.as_mut(), mock_env(), Addr::unchecked(MINTER), forged, TO_ADDR.into(), token_id.clone())
+            .unwrap();
+        assert_eq!(TO_ADDR, contract.owner_of(deps.as_ref(), mock_env(), token_id, false).unwrap().owner);
+    }
+
+    #[test]
+    fn migration() {
+        let mut deps = mock_dependencies();
+
+        set_contract_version(deps.as_mut().storage, super::CONTRACT_NAME, "0.0.1").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let version = get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(super::CONTRACT_VERSION, version.version);
+
+        // no downgrades
+        set_contract_version(deps.as_mut().storage, super::CONTRACT_NAME, "99.0.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+
+        // only this contract
+        set_contract_version(deps.as_mut().storage, "crates.io:cw721-base", "0.16.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    }
+}
@@ -6,6 +6,12 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("Invalid migration: {0}")]
+    InvalidMigration(String),
+
+    #[error("Invalid version: {0}")]
+    Version(#[from] semver::Error),
+
     // #[error("Unauthorized")]
     // Unauthorized {},
     // Add any other custom errors you like here.
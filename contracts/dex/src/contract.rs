@@ -1,19 +1,74 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    dynamic_link, entry_point,
+    callable_points, dynamic_link, entry_point,
     Addr, Binary, Contract, DepsMut, Env, MessageInfo, Response, StdResult, to_vec, Deps,
-    StakingMsg, Coin, Uint128, BankMsg, StdError
+    StakingMsg, Coin, Uint128, BankMsg, StdError, to_binary, from_binary, Order, Storage,
+    Decimal, DistributionMsg, Event, Validator, Timestamp
+};
+use crate::state::{
+    FEE, DENOM, FEES_COLLECTED, tokens, CALLEE_CONTRACT_ADDRESS, POSITION_SEQ, UNBONDING_PERIOD,
+    CLAIMS, Claim, TOTAL_WEIGHT, REWARD_INDEX, Position, OWNER, VALIDATOR_STRATEGY,
+    ValidatorStrategy, VALIDATOR_TOTALS, PositionDelegation, KEEPER, FeeConfig, OperationFee,
+    PENDING_OWNER,
+    COMPOUND_HISTORY, CompoundRecord, SHARE_TOKEN, SHARE_POOL, LOCK_TIERS, LockTier, PositionLock,
+    REWARD_CHECKPOINTS, RewardCheckpoint, EARLY_EXIT_PENALTY, PENALTY_DESTINATION,
+    PENDING_PENALTIES, PenaltyDestination, PendingPenalty, TREASURY, LIABILITIES, SLASH_EVENTS,
+    SLASH_EVENT_SEQ, POSITION_SLASHES, SlashEvent, GUARDIAN, PAUSED, PauseFlags,
+    UNAPPLIED_SLASH_WEIGHT,
+};
+use crate::fee::{calc_fee, validate_fee_config};
+use crate::strategy::{
+    select_redelegation_targets, select_validators, split_by_weight, validate_strategy,
 };
-use crate::state::{FEE, DENOM, FEES_COLLECTED, TOKENS, CALLEE_CONTRACT_ADDRESS};
 use cosmwasm_std::Attribute;
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::TokenInfoResponse;
+use cw721::OwnerOfResponse;
+use cw721_base::msg::MinterResponse;
+use cw_storage_plus::Bound;
+use semver::Version;
 
 use crate::error::ContractError;
-use crate::msg::{BurnMsg, ExecuteMsg, InstantiateMsg, MintingMsg, QueryMsg, TransferMsg};
+use crate::migrate::migrate_from_v0_1;
+use crate::msg::{
+    BurnMsg, ClaimsResponse, MigrateMsg, CompoundHistoryResponse, ExecuteMsg, InstantiateMsg, LockTierInfo,
+    LockTiersResponse, MintingMsg, NextTokenIdResponse, SlashEventInfo, SlashEventsResponse,
+    FeesCollectedResponse, TreasuryBalanceResponse, OwnershipResponse, ConfigResponse, StatusResponse,
+    PositionResponse, PositionsResponse, QueryMsg, ShareRateResponse, TransferMsg,
+    ValidatorStrategyResponse,
+    ValidatorTotalsResponse,
+};
 
 const CONTRACT_NAME: &str = "fnsa-contracts:staking-bond-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// pagination for position queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// positions compounded by one Compound
+const DEFAULT_COMPOUND_LIMIT: u32 = 30;
+const MAX_COMPOUND_LIMIT: u32 = 100;
+
+// expired locks ended by one reward index update
+const EXPIRED_LOCK_LIMIT: usize = 30;
+
+// cosmos-sdk default unbonding time, 21 days
+const DEFAULT_UNBONDING_PERIOD: u64 = 21 * 24 * 60 * 60;
+// claims can't be held for longer than this
+const MAX_UNBONDING_PERIOD: u64 = 60 * 24 * 60 * 60;
+
+// reward checkpoints are this far apart, the apr is measured over one to two of them
+const APR_WINDOW: u64 = 7 * 24 * 60 * 60;
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+// token_id of the share pool in COMPOUND_HISTORY and Rebalance
+const SHARE_POOL_ID: &str = "share_pool";
+
+// delegations are tracked in shares on chain, so one can read a few units
+// under what was delegated without being slashed
+const SLASH_TOLERANCE: Uint128 = Uint128::new(10);
+
 #[derive(Contract)]
 struct CalleeContract {
     address: Addr,
@@ -21,15 +76,29 @@ struct CalleeContract {
 
 #[dynamic_link(CalleeContract)]
 trait Callee: Contract {
-    fn mint(&self, msg: MintingMsg) -> Vec<Attribute>;
-    fn transfer_nft(&self, info: MessageInfo, recipient: String, token_id: String) -> Vec<Attribute>;
+    fn mint(&self, msg: MintingMsg) -> StdResult<Vec<Attribute>>;
+    fn transfer_nft(&self, info: MessageInfo, recipient: String, token_id: String) -> StdResult<Vec<Attribute>>;
     fn burn(&self, token_id: String) -> Vec<Attribute>;
     fn minter(&self) -> StdResult<Binary>;
     fn owner_of(&self, token_id: String, include_expired: bool, ) -> StdResult<Binary>;
+    fn is_approved(&self, token_id: String, spender: String) -> StdResult<bool>;
     fn caller_address(&self) -> Addr;
     fn call_caller_address_of(&self, addr: Addr) -> Addr;
 }
 
+#[derive(Contract)]
+struct ShareTokenContract {
+    address: Addr,
+}
+
+#[dynamic_link(ShareTokenContract)]
+trait ShareToken: Contract {
+    fn mint(&self, recipient: String, amount: Uint128) -> StdResult<Vec<Attribute>>;
+    fn burn_from(&self, owner: String, amount: Uint128) -> StdResult<Vec<Attribute>>;
+    fn token_info(&self) -> StdResult<Binary>;
+    fn minter(&self) -> StdResult<Binary>;
+}
+
 // Note, you can use StdResult in some functions where you do not
 // make use of the custom errors
 #[entry_point]
@@ -41,23 +110,96 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    OWNER.save(deps.storage, &_info.sender)?;
+
     let callee_contract_address : Addr = msg.callee_contract_address;
     CALLEE_CONTRACT_ADDRESS.save(deps.storage, &callee_contract_address.to_string())?;
 
-    let fee : String = msg.fee;
+    let fee : FeeConfig = msg.fee;
+    validate_fee_config(&fee)?;
     FEE.save(deps.storage, &fee)?;
 
     let denom : String = msg.denom;
     DENOM.save(deps.storage, &denom)?;
 
+    let unbonding_period : u64 = msg.unbonding_period.unwrap_or(DEFAULT_UNBONDING_PERIOD);
+    validate_unbonding_period(unbonding_period)?;
+    UNBONDING_PERIOD.save(deps.storage, &unbonding_period)?;
+
+    TOTAL_WEIGHT.save(deps.storage, &Uint128::zero())?;
+    REWARD_INDEX.save(deps.storage, &Decimal::zero())?;
+    REWARD_CHECKPOINTS.save(deps.storage, &vec![RewardCheckpoint {
+        time: _env.block.time,
+        index: Decimal::zero(),
+    }])?;
+
+    let validator_strategy = msg.validator_strategy.unwrap_or(ValidatorStrategy::LowestCommission {});
+    validate_strategy(&validator_strategy)?;
+    VALIDATOR_STRATEGY.save(deps.storage, &validator_strategy)?;
+
+    let lock_tiers : Vec<LockTier> = msg.lock_tiers.unwrap_or_default();
+    validate_lock_tiers(&lock_tiers)?;
+    LOCK_TIERS.save(deps.storage, &lock_tiers)?;
+
+    let early_exit_penalty : Decimal = msg.early_exit_penalty.unwrap_or_default();
+    if early_exit_penalty > Decimal::one() {
+        return Err(ContractError::InvalidPenalty {});
+    }
+    EARLY_EXIT_PENALTY.save(deps.storage, &early_exit_penalty)?;
+    PENALTY_DESTINATION.save(deps.storage, &msg.penalty_destination.unwrap_or(PenaltyDestination::Stakers {}))?;
+    PENDING_PENALTIES.save(deps.storage, &vec![])?;
+    PAUSED.save(deps.storage, &PauseFlags::default())?;
+
     Ok(Response::new()
         .add_attribute("action", "instantiate")
+        .add_attribute("owner", _info.sender)
         .add_attribute("callee_contract_address", callee_contract_address.clone())
-        .add_attribute("fee", fee.clone())
+        .add_attribute("fee", format!("{:?}", fee))
         .add_attribute("denom", denom.clone())
+        .add_attribute("unbonding_period", unbonding_period.to_string())
+        .add_attribute("early_exit_penalty", early_exit_penalty.to_string())
     )
 }
 
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration(format!(
+            "cannot migrate {} to {}",
+            stored.contract, CONTRACT_NAME,
+        )));
+    }
+    let from = Version::parse(&stored.version)?;
+    if from > Version::parse(CONTRACT_VERSION)? {
+        return Err(ContractError::InvalidMigration(format!(
+            "cannot downgrade from {} to {}",
+            stored.version, CONTRACT_VERSION,
+        )));
+    }
+
+    // state migrations, oldest first
+    if from < Version::new(0, 2, 0) {
+        let owner = msg
+            .owner
+            .ok_or_else(|| ContractError::InvalidMigration("owner is required from 0.1".into()))?;
+        let owner = deps.api.addr_validate(&owner)?;
+        let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+        let contract = CalleeContract { address: Addr::unchecked(address) };
+        let nft_owner = |token_id: &str| -> StdResult<Addr> {
+            let owner : OwnerOfResponse = from_binary(&contract.owner_of(token_id.to_string(), true)?)?;
+            Ok(Addr::unchecked(owner.owner))
+        };
+        migrate_from_v0_1(deps.storage, &env, owner, DEFAULT_UNBONDING_PERIOD, nft_owner)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 // And declare a custom Error variant for the ones where you will want to make use of it
 #[entry_point]
 pub fn execute(
@@ -68,6 +210,8 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     use ExecuteMsg::*;
 
+    assert_not_paused(deps.as_ref(), &msg)?;
+
     match msg {
         ExecuteMsg::CallCallerAddressOf { target } => {
             try_call_caller_address_of(deps.as_ref(), _env, target)
@@ -75,9 +219,38 @@ pub fn execute(
         ExecuteMsg::Transfer(msg) => {
             try_transfer(deps, _info, msg)
         },
-        Stake { } => exec::stake(deps, _env, _info).map_err(Into::into),
-        Reward { token_id } => exec::unstake(deps, _env, _info, token_id).map_err(Into::into),
-        Swap { denom_to } => exec::swap(deps, _env, _info, denom_to).map_err(Into::into),
+        Stake { validator, liquid: Some(true), lock_duration } => {
+            exec::stake_shares(deps, _env, _info, validator, lock_duration)
+        },
+        Stake { validator, lock_duration, .. } => exec::stake(deps, _env, _info, validator, lock_duration),
+        StakeMore { token_id } => exec::stake_more(deps, _env, _info, token_id),
+        Reward { token_id } => exec::unstake(deps, _env, _info, token_id, false),
+        Unbond { token_id } => exec::unstake(deps, _env, _info, token_id, false),
+        EmergencyUnbond { token_id } => exec::unstake(deps, _env, _info, token_id, true),
+        PartialUnbond { token_id, amount } => exec::partial_unbond(deps, _env, _info, token_id, amount),
+        Split { token_id, amounts } => exec::split(deps, _env, _info, token_id, amounts),
+        Merge { token_ids, into } => exec::merge(deps, _env, _info, token_ids, into),
+        SetCompounding { token_id, enabled } => exec::set_compounding(deps, _env, _info, token_id, enabled),
+        Compound { start_after, limit } => exec::compound(deps, _env, _info, start_after, limit),
+        Redeem { amount } => exec::redeem(deps, _env, _info, amount),
+        UpdateConfig { fee, denom, callee_contract_address, unbonding_period, share_token } => {
+            exec::update_config(deps, _env, _info, fee, denom, callee_contract_address, unbonding_period, share_token)
+        },
+        UpdateLockTiers { tiers } => exec::update_lock_tiers(deps, _info, tiers),
+        UpdatePenaltyConfig { early_exit_penalty, destination } => {
+            exec::update_penalty_config(deps, _info, early_exit_penalty, destination)
+        },
+        Claim {} => exec::claim(deps, _env, _info),
+        Swap { denom_to } => exec::swap(deps, _env, _info, denom_to),
+        WithdrawFees { denom, amount, recipient } => exec::withdraw_fees(deps, _info, denom, amount, recipient),
+        UpdateValidatorStrategy { strategy } => exec::update_validator_strategy(deps, _info, strategy),
+        UpdateKeeper { keeper } => exec::update_keeper(deps, _info, keeper),
+        UpdateGuardian { guardian } => exec::update_guardian(deps, _info, guardian),
+        SetPaused { stake, unstake, swap } => exec::set_paused(deps, _info, stake, unstake, swap),
+        ProposeNewOwner { new_owner } => exec::propose_new_owner(deps, _info, new_owner),
+        AcceptOwnership {} => exec::accept_ownership(deps, _info),
+        RenounceOwnership {} => exec::renounce_ownership(deps, _info),
+        Rebalance { token_ids } => exec::rebalance(deps, _env, _info, token_ids),
         
     }
 }
@@ -85,15 +258,27 @@ pub fn execute(
 mod exec {
     use super::*;
 
-    pub fn stake(deps: DepsMut, _env: Env, _info: MessageInfo) -> StdResult<Response> {
+    pub fn stake(mut deps: DepsMut, _env: Env, _info: MessageInfo, validator: Option<String>, lock_duration: Option<u64>) -> Result<Response, ContractError> {
 
         // get current block height
-        let curr_block_height_raw : u64 = _env.block.height;
-        let curr_block_height : String = curr_block_height_raw.to_string();
+        let curr_block_height : u64 = _env.block.height;
+
+        // lock the position with the chosen tier
+        let lock : Option<PositionLock> = lock_duration
+            .map(|duration| to_position_lock(deps.as_ref(), &_env, duration))
+            .transpose()?;
+
+        // get amount for staking
+        let (denom, fee, amount_to_stake) = parse_stake_funds(deps.as_ref(), &_info)?;
+
+        // pick validators with the configured strategy
+        let delegations = select_validators(deps.branch(), validator, amount_to_stake)?;
+
+        // settle rewards before the bonded total changes
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
 
         // create token_id
-        let mut token_id : String = "cw721_".into();
-        token_id.push_str(&curr_block_height);
+        let token_id : String = reserve_token_id(deps.storage)?;
 
         // mint nft
         let minting_msg = MintingMsg {
@@ -103,7 +288,7 @@ mod exec {
         };
         let cw721_contract_address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
         let cw721_contract = CalleeContract { address: Addr::unchecked(cw721_contract_address.clone()) };
-        let mint_res = cw721_contract.mint(minting_msg.clone());
+        let mint_res = cw721_contract.mint(minting_msg.clone())?;
         
         // get mint result
         let mint_res_keys = mint_res
@@ -119,101 +304,66 @@ mod exec {
             .collect::<Vec<String>>()
             .join(",");
 
-        // get the validator with the lowest commission
-        let res = deps.querier.query_all_validators()?;
-        let vec_validators = res.clone();
-        let mut validator = vec_validators[0].clone();
-        for validator_ in vec_validators {
-            if validator.commission < validator_.commission {
-                validator = validator_;
-            }
-        }
-
-        let info_clone = _info.clone();
-
-        // get fee
-        let fee_raw : String = FEE.load(deps.storage)?;
-        let fee : Uint128 = fee_raw.parse::<Uint128>().unwrap();
-
-        // get denom
-        let denom : String = DENOM.load(deps.storage)?;
-
-        // get fund
-        let payment = info_clone
-            .funds
-            .iter()
-            .find(|coin| coin.denom == denom)
-            .ok_or_else(|| StdError::generic_err(format!("invalid denom received")))?;
-        let fund : Uint128 = payment.amount;
-
-        // error if fund is equal or smaller than fee
-        if fund <= fee {
-            return Err(StdError::generic_err(format!(
-                "fund: {} should be greater than fee: {}",
-                fund,
-                fee,
-            )));
-        }
-
-        // get amount for staking
-        let amount_to_stake: Uint128 = fund - fee;
-
         // stake coin
         let res = Response::new()
             .add_attribute("action", "stake")
             .add_attribute("sender", _info.clone().sender)
             .add_attribute("amount_to_stake", amount_to_stake.clone().to_string())
             .add_attribute("denom", denom.clone())
+            .add_attribute("lock_duration", lock_duration.unwrap_or_default().to_string())
             .add_attribute("mint_res_keys", mint_res_keys)
             .add_attribute("mint_res_values", mint_res_values)
-            .add_message(StakingMsg::Delegate {
-                validator: validator.clone().address,
+            .add_messages(withdraw_msgs)
+            .add_messages(delegations.iter().map(|delegation| StakingMsg::Delegate {
+                validator: delegation.validator.clone(),
                 amount: Coin {
                     denom: denom.clone(),
-                    amount: amount_to_stake.clone(),
+                    amount: delegation.amount,
                 }
-            });
+            }));
         
         // // update storage
-        TOKENS.save( // to be replaced by TOKENS
-            deps.storage,
-            token_id.clone(),
-            &(amount_to_stake, validator.clone().address, curr_block_height)
-        )?;
-        FEES_COLLECTED.update(
-            deps.storage,
-            &_info.clone().sender,
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default().checked_add(fee)?)
-            },
-        )?;
+        let position = Position {
+            owner: _info.sender.clone(),
+            principal: amount_to_stake,
+            denom: denom.clone(),
+            delegations: delegations.clone(),
+            start_height: curr_block_height,
+            start_time: _env.block.time,
+            reward_index,
+            accrued_reward: Uint128::zero(),
+            compounding: false,
+            lock,
+            slash_seq: SLASH_EVENT_SEQ.may_load(deps.storage)?.unwrap_or_default(),
+        };
+        tokens().save(deps.storage, token_id.clone(), &position)?;
+        update_total_weight(deps.storage, Uint128::zero(), position_weight(&position, _env.block.time))?;
+        add_validator_totals(deps.storage, &delegations)?;
+        collect_fee(deps.storage, &denom, fee)?;
 
         Ok(res)
     }
 
-    pub fn unstake(deps: DepsMut,  _env: Env, info: MessageInfo, token_id: String) -> StdResult<Response> {
+    // emergency lets a locked position exit early, minus the early exit penalty
+    pub fn unstake(mut deps: DepsMut,  _env: Env, info: MessageInfo, token_id: String, emergency: bool) -> Result<Response, ContractError> {
 
-        // get current block height
-        let curr_block_height_raw : u64 = _env.block.height;
-        let curr_block_height : String = curr_block_height_raw.to_string();
-
-        // get data for unstaking // to be replaced by TOKENS
-        let values : (Uint128, String, String) = TOKENS.load(deps.storage, token_id.clone())?;
-        let amount_to_unstake : Uint128 = values.0.clone();
-        let validator_address : String = values.1.clone();
-        let prev_block_height : String = values.2.clone();
-        
-        // calculate reward by height difference
-        let diff_height : Uint128 = curr_block_height.parse::<Uint128>().unwrap() - prev_block_height.parse::<Uint128>().unwrap();
-        let reward = diff_height + amount_to_unstake; // reward : diff_height = 1 : 1
-
-        // error if amount_to_unstake is zero or below zero
-        if amount_to_unstake <= Uint128::from(0u128) {
-            return Err(StdError::generic_err(format!(
-                "amount_to_unstake: {} should be greater than 0",
-                amount_to_unstake.clone(),
-            )));
+        // withdraw delegation rewards, slashes are applied when the position is loaded
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+
+        // get data for unstaking
+        let mut position : Position = load_position(deps.storage, &_env, &token_id)?;
+        let amount_to_unstake : Uint128 = position.principal;
+
+        // only the nft owner, an approved spender or an operator can take the reward
+        assert_nft_owner(deps.as_ref(), &token_id, &position, &info.sender)?;
+        if !emergency {
+            assert_unlocked(&_env, &position)?;
         }
+        let penalty : Uint128 = calc_early_exit_penalty(deps.as_ref(), &_env, &position)?;
+
+        // calculate this position's share of the rewards
+        settle_position(&mut position, reward_index, _env.block.time);
+        let reward = position.accrued_reward;
 
         // burn token - dynamic call
         let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
@@ -234,197 +384,3278 @@ mod exec {
             .collect::<Vec<String>>()
             .join(",");
 
-        // unstake and send
-        let res = Response::new()
-            .add_attribute("action", "reward")
+        // undelegated coins arrive after the unbonding period, so the principal
+        // is paid out by a later claim
+        let denom : String = DENOM.load(deps.storage)?;
+        let unbonding_period : u64 = UNBONDING_PERIOD.load(deps.storage)?;
+        let release_at = _env.block.time.plus_seconds(unbonding_period);
+
+        // unstake and record claim
+        let mut res = Response::new()
+            .add_attribute("action", if emergency { "emergency_unbond" } else { "reward" })
             .add_attribute("sender", info.clone().sender)
             .add_attribute("amount_to_unstake", amount_to_unstake.clone().to_string())
             .add_attribute("reward", reward.clone().to_string())
+            .add_attribute("penalty", penalty.to_string())
+            .add_attribute("release_at", release_at.to_string())
             .add_attribute("burn_res_keys", burn_res_keys)
             .add_attribute("burn_res_values", burn_res_values)
-            .add_message(StakingMsg::Undelegate {
-                validator: validator_address.clone(),
-                amount: Coin {
-                    denom: "ucony".into(),
-                    amount: amount_to_unstake.clone(),
-                }
-            })
-            .add_message(BankMsg::Send {
+            .add_messages(withdraw_msgs)
+            // a fully slashed position has nothing left to undelegate, it is
+            // only closed and its reward paid out
+            .add_messages(position.delegations
+                .iter()
+                .filter(|delegation| !delegation.amount.is_zero())
+                .map(|delegation| StakingMsg::Undelegate {
+                    validator: delegation.validator.clone(),
+                    amount: Coin {
+                        denom: denom.clone(),
+                        amount: delegation.amount,
+                    }
+                }));
+
+        // withdrawn rewards are liquid, so they are paid out right away
+        let reward_fee : Uint128 = calc_fee(&FEE.load(deps.storage)?.performance, &denom, reward).min(reward);
+        sub_liability(deps.storage, &denom, reward)?;
+        collect_fee(deps.storage, &denom, reward_fee)?;
+        res = res.add_attribute("reward_fee", reward_fee.to_string());
+        if reward > reward_fee {
+            res = res.add_message(BankMsg::Send {
                 to_address: info.clone().sender.into_string(),
                 amount: vec![Coin {
-                    denom: "ucony".into(),
-                    amount: reward.clone(),
+                    denom: denom.clone(),
+                    amount: reward - reward_fee,
                 }]
             });
+        }
 
         // burn token
-        TOKENS.remove(
+        tokens().remove(
             deps.storage,
             token_id.clone(),
-        );
+        )?;
+        update_total_weight(deps.storage, position_weight(&position, _env.block.time), Uint128::zero())?;
+        sub_validator_totals(deps.storage, &position.delegations)?;
+        if amount_to_unstake > penalty {
+            push_claim(deps.storage, &info.sender, Claim {
+                amount: amount_to_unstake - penalty,
+                denom: denom.clone(),
+                release_at,
+            })?;
+        }
+
+        // the penalty unbonds with the rest and is handed out once it's liquid
+        if !penalty.is_zero() {
+            let destination : PenaltyDestination = PENALTY_DESTINATION.load(deps.storage)?;
+            res = res.add_event(Event::new("early_exit_penalty")
+                .add_attribute("token_id", token_id)
+                .add_attribute("amount", penalty.to_string())
+                .add_attribute("denom", denom.clone())
+                .add_attribute("destination", format!("{:?}", destination)));
+            add_liability(deps.storage, &denom, penalty)?;
+            PENDING_PENALTIES.update(deps.storage, |mut penalties| -> StdResult<_> {
+                penalties.push(PendingPenalty {
+                    amount: penalty,
+                    denom,
+                    release_at,
+                    destination,
+                });
+                Ok(penalties)
+            })?;
+        }
 
         Ok(res)
     }
 
-    pub fn swap(deps : DepsMut, _env: Env, info: MessageInfo, denom_to: String) -> StdResult<Response> {
+    pub fn partial_unbond(mut deps: DepsMut, _env: Env, info: MessageInfo, token_id: String, amount: Uint128) -> Result<Response, ContractError> {
 
-        let info_clone = info.clone();
+        // withdraw delegation rewards, slashes are applied when the position is loaded
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
 
-        // get fee
-        let fee_raw : String = FEE.load(deps.storage)?;
-        let fee : Uint128 = fee_raw.parse::<Uint128>().unwrap();
-        
-        // get fund
-        let payment = info_clone // fund should be single
-            .funds
-            .iter()
-            .find(|_coin| true)
-            .ok_or_else(|| StdError::generic_err(format!("invalid denom received")))?;
-        let fund : Uint128 = payment.amount;
-        
-        // get denom_to
-        if payment.clone().denom == denom_to.clone() {
-            return Err(StdError::generic_err(format!(
-                "denom_to should not be the same as denom_from",
-            )));
-        }
+        // get data for unstaking
+        let mut position : Position = load_position(deps.storage, &_env, &token_id)?;
+        assert_nft_owner(deps.as_ref(), &token_id, &position, &info.sender)?;
+        assert_unlocked(&_env, &position)?;
 
-        // error if fund is equal or smaller than fee
-        if fund <= fee {
-            return Err(StdError::generic_err(format!(
-                "fund: {} should be greater than fee: {}",
-                fund,
-                fee,
-            )));
+        // the nft is kept, so something has to stay bonded
+        if amount.is_zero() || amount >= position.principal {
+            return Err(ContractError::InvalidAmount { available: position.principal });
         }
 
-        let amount_to_swap = fund - fee;
+        // settle the reward earned on the current amount
+        settle_position(&mut position, reward_index, _env.block.time);
 
-        // query ubrown balance
-        let contract_address : String = _env.contract.address.into_string();
-        let res = deps.querier.query_balance(contract_address, denom_to.clone())?;
-        let denom_to_balance = res.clone().amount;
-        // error if swap amount requested is greater than denom_to_balance
-        if denom_to_balance < amount_to_swap {
-            return Err(StdError::generic_err(format!(
-                "short of contract's ubrown reserve: {}ubrown",
-                denom_to_balance,
-            )));
-        }
+        // undelegate from every validator in proportion
+        let (remaining, taken) = take_delegations(&position.delegations, amount)?;
+        let denom : String = DENOM.load(deps.storage)?;
+        let unbonding_period : u64 = UNBONDING_PERIOD.load(deps.storage)?;
+        let release_at = _env.block.time.plus_seconds(unbonding_period);
 
-        // send to sender
         let res = Response::new()
-            .add_attribute("action", "swap")
+            .add_attribute("action", "partial_unbond")
             .add_attribute("sender", info.clone().sender)
-            .add_attribute("amount", amount_to_swap.clone().to_string())
-            .add_attribute("denom_to", denom_to.clone())
-            .add_message(BankMsg::Send {
-                to_address: info.clone().sender.into_string(),
-                amount: vec![Coin {
-                    denom: denom_to.clone(),
-                    amount: amount_to_swap.clone(),
-                }]
-            });
+            .add_attribute("token_id", token_id.clone())
+            .add_attribute("amount_to_unstake", amount.to_string())
+            .add_attribute("release_at", release_at.to_string())
+            .add_messages(withdraw_msgs)
+            .add_messages(taken.iter().map(|delegation| StakingMsg::Undelegate {
+                validator: delegation.validator.clone(),
+                amount: Coin {
+                    denom: denom.clone(),
+                    amount: delegation.amount,
+                }
+            }));
+
+        // update storage
+        sub_validator_totals(deps.storage, &taken)?;
+        let weight : Uint128 = position_weight(&position, _env.block.time);
+        position.principal -= amount;
+        position.delegations = remaining;
+        tokens().save(deps.storage, token_id, &position)?;
+        update_total_weight(deps.storage, weight, position_weight(&position, _env.block.time))?;
+        push_claim(deps.storage, &info.sender, Claim {
+            amount,
+            denom,
+            release_at,
+        })?;
+
         Ok(res)
     }
 
-}
-
+    pub fn split(mut deps: DepsMut, _env: Env, info: MessageInfo, token_id: String, amounts: Vec<Uint128>) -> Result<Response, ContractError> {
 
-pub fn try_mint(
-    deps: DepsMut,
-    msg: MintingMsg
-) -> Result<Response, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    let mint_res = contract.mint(msg.clone());
-    let res = Response::default()
-        .add_attributes(mint_res);
+        // withdraw delegation rewards, slashes are applied when the position is loaded
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let (owner, new_token_ids) = split_position(deps.branch(), &_env, &info.sender, &token_id, amounts, reward_index)?;
 
-    Ok(res)
-}
+        let cw721_contract_address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+        let cw721_contract = CalleeContract { address: Addr::unchecked(cw721_contract_address) };
 
-pub fn try_transfer(
-    deps: DepsMut,
-    info: MessageInfo,
-    msg: TransferMsg,
-) -> Result<Response, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    let transfer_res = contract.transfer_nft(info, msg.clone().recipient, msg.clone().token_id);
-    let res = Response::default()
-        .add_attributes(transfer_res);
+        let mut res = Response::new()
+            .add_attribute("action", "split")
+            .add_attribute("sender", info.sender)
+            .add_attribute("token_id", token_id)
+            .add_messages(withdraw_msgs);
 
-    Ok(res)
-}
+        // mint nft for the new positions
+        for new_token_id in new_token_ids.iter() {
+            let mint_res = cw721_contract.mint(MintingMsg {
+                token_id: new_token_id.clone(),
+                owner: owner.to_string(),
+                token_uri: "https://www.finschia.network/".into(),
+            })?;
+            res = res.add_attributes(mint_res);
+        }
 
-pub fn try_burn(
-    deps: DepsMut,
-    msg: BurnMsg
-) -> Result<Response, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    let burn_res = contract.burn(msg.clone().token_id);
-    let res = Response::default()
-        .add_attributes(burn_res);
-    Ok(res)
-}
+        Ok(res.add_attribute("new_token_ids", new_token_ids.join(",")))
+    }
 
-// check the caller_address works
-pub fn try_call_caller_address_of(
-    deps: Deps,
-    _env: Env,
-    target: Addr,
-) -> Result<Response, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    let result_addr = contract.call_caller_address_of(target);
-    let res = Response::default().add_attribute(
-        "call_caller_address_is_as_expected",
-        (result_addr == address).to_string(),
-    );
-    Ok(res)
-}
+    pub fn merge(mut deps: DepsMut, _env: Env, info: MessageInfo, token_ids: Vec<String>, into: String) -> Result<Response, ContractError> {
 
-#[entry_point]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
-    match msg {
-        QueryMsg::GetOwnAddressViaCalleesGetCallerAddress {} => {
-            get_own_address_via_callees_get_caller_address(deps, env)
+        // get data for merging
+        if token_ids.is_empty() {
+            return Err(ContractError::InvalidMerge("no positions to merge".into()));
         }
-        QueryMsg::Minter {} => {
-            minter(deps, env)
+        for (i, token_id) in token_ids.iter().enumerate() {
+            if *token_id == into || token_ids[..i].contains(token_id) {
+                return Err(ContractError::InvalidMerge(format!("{} is listed more than once", token_id)));
+            }
         }
-        QueryMsg::OwnerOf { token_id, include_expired } => {
-            owner_of(deps, env, token_id, include_expired)
+
+        // withdraw delegation rewards, slashes are applied when the position is loaded
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let position : Position = merge_positions(deps.branch(), &_env, &info.sender, &token_ids, &into, reward_index)?;
+
+        let cw721_contract_address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+        let cw721_contract = CalleeContract { address: Addr::unchecked(cw721_contract_address) };
+
+        let mut res = Response::new()
+            .add_attribute("action", "merge")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("into", into)
+            .add_attribute("token_ids", token_ids.join(","))
+            .add_messages(withdraw_msgs);
+
+        // burn token - dynamic call
+        for token_id in token_ids {
+            let burn_res = cw721_contract.burn(token_id);
+            res = res.add_attributes(burn_res);
         }
+
+        Ok(res.add_attribute("amount", position.principal.to_string()))
     }
-}
 
-fn minter(
-    deps: Deps, _env: Env,
-) -> Result<Binary, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    Ok(Binary(to_vec(&contract.minter())?))
-}
+    pub fn set_compounding(deps: DepsMut, _env: Env, info: MessageInfo, token_id: String, enabled: bool) -> Result<Response, ContractError> {
+        let mut position : Position = load_position(deps.storage, &_env, &token_id)?;
+        assert_nft_owner(deps.as_ref(), &token_id, &position, &info.sender)?;
 
-fn owner_of(deps: Deps, _env: Env,
-    token_id: String,
-    include_expired: bool
-) -> Result<Binary, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    Ok(Binary(to_vec(&contract.owner_of(token_id, include_expired))?))
-}
+        position.compounding = enabled;
+        tokens().save(deps.storage, token_id.clone(), &position)?;
 
-fn get_own_address_via_callees_get_caller_address(
-    deps: Deps,
-    _env: Env,
-) -> Result<Binary, ContractError> {
-    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
-    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
-    Ok(Binary(to_vec(&contract.caller_address())?))
+        let res = Response::new()
+            .add_attribute("action", "set_compounding")
+            .add_attribute("sender", info.sender)
+            .add_attribute("token_id", token_id)
+            .add_attribute("enabled", enabled.to_string());
+        Ok(res)
+    }
+
+    pub fn compound(
+        mut deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+
+        // withdraw rewards, they are liquid before the delegations below run
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let performance_fee : OperationFee = FEE.load(deps.storage)?.performance;
+        let denom : String = DENOM.load(deps.storage)?;
+
+        let limit = limit.unwrap_or(DEFAULT_COMPOUND_LIMIT).min(MAX_COMPOUND_LIMIT) as usize;
+        let first_page : bool = start_after.is_none();
+        let compounding : Vec<(String, Position)> = tokens()
+            .idx
+            .compounding
+            .prefix(1)
+            .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+        let last_token_id : Option<String> = compounding.last().map(|(token_id, _)| token_id.clone());
+
+        let mut total_compounded = Uint128::zero();
+        let mut total_fee = Uint128::zero();
+        let mut added : Vec<PositionDelegation> = vec![];
+        for (token_id, mut position) in compounding {
+            refresh_position(deps.storage, &_env, &token_id, &mut position)?;
+            let compounded = compound_position(
+                deps.storage,
+                &_env,
+                &token_id,
+                &mut position,
+                reward_index,
+                &performance_fee,
+            )?;
+            tokens().save(deps.storage, token_id, &position)?;
+            if let Some((delegations, compounded, fee)) = compounded {
+                added.extend(delegations);
+                total_compounded += compounded;
+                total_fee += fee;
+            }
+        }
+
+        // one delegation per validator for all positions together
+        let added = merge_delegations(added);
+        add_validator_totals(deps.storage, &added)?;
+
+        // the share pool always compounds, once per round of pages
+        let pool_msgs : Vec<StakingMsg> = match SHARE_POOL.may_load(deps.storage)? {
+            Some(_) if first_page => compound_share_pool(deps.storage, &_env, reward_index)?.1,
+            _ => vec![],
+        };
+
+        let res = Response::new()
+            .add_attribute("action", "compound")
+            .add_attribute("sender", info.sender)
+            .add_attribute("compounded", total_compounded.to_string())
+            .add_attribute("fee", total_fee.to_string())
+            .add_attribute("last_token_id", last_token_id.unwrap_or_default())
+            .add_messages(withdraw_msgs)
+            .add_messages(pool_msgs)
+            .add_messages(added.into_iter().map(|delegation| StakingMsg::Delegate {
+                validator: delegation.validator,
+                amount: Coin {
+                    denom: denom.clone(),
+                    amount: delegation.amount,
+                }
+            }));
+        Ok(res)
+    }
+
+    pub fn stake_shares(mut deps: DepsMut, _env: Env, info: MessageInfo, validator: Option<String>, lock_duration: Option<u64>) -> Result<Response, ContractError> {
+
+        // shares are liquid, so they can't be locked
+        if lock_duration.is_some() {
+            return Err(ContractError::LiquidStakeLocked {});
+        }
+
+        // get amount for staking
+        let share_token = load_share_token(deps.as_ref())?;
+        let (denom, fee, amount_to_stake) = parse_stake_funds(deps.as_ref(), &info)?;
+        let delegations = select_validators(deps.branch(), validator, amount_to_stake)?;
+
+        // compound the pool first so the exchange rate includes its rewards
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let (mut pool, compound_msgs) = compound_share_pool(deps.storage, &_env, reward_index)?;
+
+        // mint shares at the current exchange rate
+        let supply : Uint128 = share_supply(&share_token)?;
+        let shares : Uint128 = shares_for_amount(amount_to_stake, supply, pool.principal)?;
+        let mint_res = share_token.mint(info.sender.to_string(), shares)?;
+
+        let res = Response::new()
+            .add_attribute("action", "stake_shares")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("amount_to_stake", amount_to_stake.to_string())
+            .add_attribute("denom", denom.clone())
+            .add_attribute("shares", shares.to_string())
+            .add_attributes(mint_res)
+            .add_messages(withdraw_msgs)
+            .add_messages(compound_msgs)
+            .add_messages(delegations.iter().map(|delegation| StakingMsg::Delegate {
+                validator: delegation.validator.clone(),
+                amount: Coin {
+                    denom: denom.clone(),
+                    amount: delegation.amount,
+                }
+            }));
+
+        // update storage
+        add_validator_totals(deps.storage, &delegations)?;
+        let weight : Uint128 = position_weight(&pool, _env.block.time);
+        pool.principal = pool.principal.checked_add(amount_to_stake)?;
+        pool.delegations = merge_delegations([pool.delegations, delegations].concat());
+        SHARE_POOL.save(deps.storage, &pool)?;
+        update_total_weight(deps.storage, weight, position_weight(&pool, _env.block.time))?;
+        collect_fee(deps.storage, &denom, fee)?;
+
+        Ok(res)
+    }
+
+    pub fn redeem(mut deps: DepsMut, _env: Env, info: MessageInfo, amount: Uint128) -> Result<Response, ContractError> {
+
+        // compound the pool first so the exchange rate includes its rewards
+        let share_token = load_share_token(deps.as_ref())?;
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let (mut pool, compound_msgs) = compound_share_pool(deps.storage, &_env, reward_index)?;
+
+        // value of the shares at the current exchange rate
+        let supply : Uint128 = share_supply(&share_token)?;
+        let amount_to_unstake : Uint128 = amount_for_shares(amount, supply, pool.principal)?;
+
+        // burn shares - dynamic call, fails when the sender holds too few
+        let burn_res = share_token.burn_from(info.sender.to_string(), amount)?;
+
+        let (remaining, taken) = take_delegations(&pool.delegations, amount_to_unstake)?;
+        let denom : String = DENOM.load(deps.storage)?;
+        let unbonding_period : u64 = UNBONDING_PERIOD.load(deps.storage)?;
+        let release_at = _env.block.time.plus_seconds(unbonding_period);
+
+        let res = Response::new()
+            .add_attribute("action", "redeem")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("shares", amount.to_string())
+            .add_attribute("amount_to_unstake", amount_to_unstake.to_string())
+            .add_attribute("release_at", release_at.to_string())
+            .add_attributes(burn_res)
+            .add_messages(withdraw_msgs)
+            .add_messages(compound_msgs)
+            .add_messages(taken.iter().map(|delegation| StakingMsg::Undelegate {
+                validator: delegation.validator.clone(),
+                amount: Coin {
+                    denom: denom.clone(),
+                    amount: delegation.amount,
+                }
+            }));
+
+        // update storage
+        sub_validator_totals(deps.storage, &taken)?;
+        let weight : Uint128 = position_weight(&pool, _env.block.time);
+        pool.principal -= amount_to_unstake;
+        pool.delegations = remaining;
+        SHARE_POOL.save(deps.storage, &pool)?;
+        update_total_weight(deps.storage, weight, position_weight(&pool, _env.block.time))?;
+        push_claim(deps.storage, &info.sender, Claim {
+            amount: amount_to_unstake,
+            denom,
+            release_at,
+        })?;
+
+        Ok(res)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_config(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        fee: Option<FeeConfig>,
+        denom: Option<String>,
+        callee_contract_address: Option<String>,
+        unbonding_period: Option<u64>,
+        share_token: Option<String>,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+
+        let mut res = Response::new()
+            .add_attribute("action", "update_config")
+            .add_attribute("sender", info.sender);
+
+        if let Some(fee) = fee {
+            validate_fee_config(&fee)?;
+            let old : FeeConfig = FEE.load(deps.storage)?;
+            FEE.save(deps.storage, &fee)?;
+            res = res.add_event(config_event("fee", format!("{:?}", old), format!("{:?}", fee)));
+        }
+
+        // positions and claims would mix denoms
+        if let Some(denom) = denom {
+            if !TOTAL_WEIGHT.load(deps.storage)?.is_zero() {
+                return Err(ContractError::InvalidConfig("denom can't change while positions are bonded".into()));
+            }
+            let old : String = DENOM.load(deps.storage)?;
+            DENOM.save(deps.storage, &denom)?;
+            res = res.add_event(config_event("denom", old, denom));
+        }
+
+        // the position nfts are minted through the callee, so it has to accept
+        // this contract; the nfts of bonded positions would be left behind
+        if let Some(address) = callee_contract_address {
+            if !TOTAL_WEIGHT.load(deps.storage)?.is_zero() {
+                return Err(ContractError::InvalidConfig(
+                    "callee_contract_address can't change while positions are bonded".into(),
+                ));
+            }
+            let address = deps.api.addr_validate(&address)?;
+            let contract = CalleeContract { address: address.clone() };
+            let minter : MinterResponse = from_binary(&contract.minter()?)?;
+            if minter.minter != _env.contract.address.as_str() {
+                return Err(ContractError::InvalidConfig(format!(
+                    "minter of {} is {}, not this contract",
+                    address, minter.minter,
+                )));
+            }
+            let old : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+            CALLEE_CONTRACT_ADDRESS.save(deps.storage, &address.to_string())?;
+            res = res.add_event(config_event("callee_contract_address", old, address.into_string()));
+        }
+
+        if let Some(unbonding_period) = unbonding_period {
+            validate_unbonding_period(unbonding_period)?;
+            let old : u64 = UNBONDING_PERIOD.load(deps.storage)?;
+            UNBONDING_PERIOD.save(deps.storage, &unbonding_period)?;
+            res = res.add_event(config_event("unbonding_period", old.to_string(), unbonding_period.to_string()));
+        }
+
+        // the shares already issued are backed by the share pool, and the pool
+        // mints through the token, so it has to accept this contract
+        if let Some(share_token) = share_token {
+            let share_token = deps.api.addr_validate(&share_token)?;
+            let old = SHARE_TOKEN.may_load(deps.storage)?;
+            let bonded = SHARE_POOL.may_load(deps.storage)?.map(|pool| pool.principal).unwrap_or_default();
+            if old.as_ref() != Some(&share_token) && !bonded.is_zero() {
+                return Err(ContractError::InvalidConfig(
+                    "share_token can't change while the share pool is bonded".into(),
+                ));
+            }
+            let contract = ShareTokenContract { address: share_token.clone() };
+            let minter : Option<cw20::MinterResponse> = from_binary(&contract.minter()?)?;
+            let minter = minter.map(|minter| minter.minter).unwrap_or_default();
+            if minter != _env.contract.address.as_str() {
+                return Err(ContractError::InvalidConfig(format!(
+                    "minter of {} is {}, not this contract",
+                    share_token, minter,
+                )));
+            }
+            SHARE_TOKEN.save(deps.storage, &share_token)?;
+            res = res.add_event(config_event(
+                "share_token",
+                old.map(String::from).unwrap_or_default(),
+                share_token.into_string(),
+            ));
+        }
+
+        Ok(res)
+    }
+
+    pub fn update_lock_tiers(deps: DepsMut, info: MessageInfo, tiers: Vec<LockTier>) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+        validate_lock_tiers(&tiers)?;
+        LOCK_TIERS.save(deps.storage, &tiers)?;
+
+        let res = Response::new()
+            .add_attribute("action", "update_lock_tiers")
+            .add_attribute("sender", info.sender)
+            .add_attribute("tiers", tiers
+                .iter()
+                .map(|tier| format!("{}:{}", tier.duration, tier.multiplier))
+                .collect::<Vec<String>>()
+                .join(","));
+        Ok(res)
+    }
+
+    pub fn withdraw_fees(deps: DepsMut, info: MessageInfo, denom: String, amount: Uint128, recipient: String) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+        let recipient = deps.api.addr_validate(&recipient)?;
+
+        let balance : Uint128 = TREASURY.may_load(deps.storage, &denom)?.unwrap_or_default();
+        if amount.is_zero() || amount > balance {
+            return Err(ContractError::InsufficientTreasury { denom, available: balance });
+        }
+        if amount == balance {
+            TREASURY.remove(deps.storage, &denom);
+        } else {
+            TREASURY.save(deps.storage, &denom, &(balance - amount))?;
+        }
+
+        let res = Response::new()
+            .add_attribute("action", "withdraw_fees")
+            .add_attribute("sender", info.sender)
+            .add_attribute("recipient", recipient.clone())
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", denom.clone())
+            .add_message(BankMsg::Send {
+                to_address: recipient.into_string(),
+                amount: vec![Coin { denom, amount }],
+            });
+        Ok(res)
+    }
+
+    pub fn update_penalty_config(
+        deps: DepsMut,
+        info: MessageInfo,
+        early_exit_penalty: Option<Decimal>,
+        destination: Option<PenaltyDestination>,
+    ) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+
+        let mut res = Response::new()
+            .add_attribute("action", "update_penalty_config")
+            .add_attribute("sender", info.sender);
+        if let Some(early_exit_penalty) = early_exit_penalty {
+            if early_exit_penalty > Decimal::one() {
+                return Err(ContractError::InvalidPenalty {});
+            }
+            EARLY_EXIT_PENALTY.save(deps.storage, &early_exit_penalty)?;
+            res = res.add_attribute("early_exit_penalty", early_exit_penalty.to_string());
+        }
+        if let Some(destination) = destination {
+            res = res.add_attribute("destination", format!("{:?}", destination));
+            PENALTY_DESTINATION.save(deps.storage, &destination)?;
+        }
+        Ok(res)
+    }
+
+    pub fn stake_more(mut deps: DepsMut, _env: Env, info: MessageInfo, token_id: String) -> Result<Response, ContractError> {
+
+        // withdraw delegation rewards, slashes are applied when the position is loaded
+        let (reward_index, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+
+        // get data for staking, a lock that expired is ended when loading so
+        // the added amount isn't boosted
+        let mut position : Position = load_position(deps.storage, &_env, &token_id)?;
+        assert_nft_owner(deps.as_ref(), &token_id, &position, &info.sender)?;
+        // the lock was chosen for the amount staked with it, stake a new position instead
+        assert_unlocked(&_env, &position)?;
+        let (denom, fee, amount_to_stake) = parse_stake_funds(deps.as_ref(), &info)?;
+
+        // settle the reward earned on the current amount
+        settle_position(&mut position, reward_index, _env.block.time);
+
+        // split the funds like the position's current delegations; a fully
+        // slashed position has none left, so the strategy picks validators
+        let weights : Vec<(String, u128)> = position
+            .delegations
+            .iter()
+            .map(|delegation| (delegation.validator.clone(), delegation.amount.u128()))
+            .collect();
+        let added : Vec<PositionDelegation> = if weights.is_empty() {
+            select_validators(deps.branch(), None, amount_to_stake)?
+        } else {
+            split_by_weight(weights, amount_to_stake)?
+        };
+
+        let res = Response::new()
+            .add_attribute("action", "stake_more")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("token_id", token_id.clone())
+            .add_attribute("amount_to_stake", amount_to_stake.to_string())
+            .add_attribute("denom", denom.clone())
+            .add_messages(withdraw_msgs)
+            .add_messages(added.iter().map(|delegation| StakingMsg::Delegate {
+                validator: delegation.validator.clone(),
+                amount: Coin {
+                    denom: denom.clone(),
+                    amount: delegation.amount,
+                }
+            }));
+
+        // update storage
+        add_validator_totals(deps.storage, &added)?;
+        let weight : Uint128 = position_weight(&position, _env.block.time);
+        position.principal = position.principal.checked_add(amount_to_stake)?;
+        position.delegations = merge_delegations([position.delegations, added].concat());
+        tokens().save(deps.storage, token_id, &position)?;
+        update_total_weight(deps.storage, weight, position_weight(&position, _env.block.time))?;
+        collect_fee(deps.storage, &denom, fee)?;
+
+        Ok(res)
+    }
+
+    pub fn claim(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+
+        // split matured claims from the ones still unbonding
+        let claims : Vec<Claim> = CLAIMS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+        let (matured, pending) : (Vec<Claim>, Vec<Claim>) = claims
+            .into_iter()
+            .partition(|claim| claim.release_at <= _env.block.time);
+        if matured.is_empty() {
+            return Err(ContractError::NothingToClaim {});
+        }
+
+        // sum matured claims per denom
+        let mut amount : Vec<Coin> = vec![];
+        for claim in matured {
+            match amount.iter_mut().find(|coin| coin.denom == claim.denom) {
+                Some(coin) => coin.amount = coin.amount.checked_add(claim.amount)?,
+                None => amount.push(Coin {
+                    denom: claim.denom,
+                    amount: claim.amount,
+                }),
+            }
+        }
+
+        // the unstake fee is taken once the coins are liquid
+        let unstake_fee : OperationFee = FEE.load(deps.storage)?.unstake;
+        let mut fees : Vec<Coin> = vec![];
+        for coin in amount.iter_mut() {
+            sub_liability(deps.storage, &coin.denom, coin.amount)?;
+            let fee : Uint128 = calc_fee(&unstake_fee, &coin.denom, coin.amount).min(coin.amount);
+            collect_fee(deps.storage, &coin.denom, fee)?;
+            coin.amount -= fee;
+            fees.push(Coin {
+                denom: coin.denom.clone(),
+                amount: fee,
+            });
+        }
+        amount.retain(|coin| !coin.amount.is_zero());
+
+        // update storage
+        if pending.is_empty() {
+            CLAIMS.remove(deps.storage, &info.sender);
+        } else {
+            CLAIMS.save(deps.storage, &info.sender, &pending)?;
+        }
+
+        let mut res = Response::new()
+            .add_attribute("action", "claim")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("amount", amount.iter().map(|coin| coin.to_string()).collect::<Vec<String>>().join(","))
+            .add_attribute("fee", fees.iter().map(|coin| coin.to_string()).collect::<Vec<String>>().join(","));
+        if !amount.is_empty() {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.into_string(),
+                amount,
+            });
+        }
+        Ok(res)
+    }
+
+    pub fn update_validator_strategy(deps: DepsMut, info: MessageInfo, strategy: ValidatorStrategy) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+        validate_strategy(&strategy)?;
+        VALIDATOR_STRATEGY.save(deps.storage, &strategy)?;
+
+        let res = Response::new()
+            .add_attribute("action", "update_validator_strategy")
+            .add_attribute("sender", info.sender)
+            .add_attribute("strategy", format!("{:?}", strategy));
+        Ok(res)
+    }
+
+    pub fn update_keeper(deps: DepsMut, info: MessageInfo, keeper: Option<String>) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+
+        let keeper = keeper.map(|keeper| deps.api.addr_validate(&keeper)).transpose()?;
+        match &keeper {
+            Some(keeper) => KEEPER.save(deps.storage, keeper)?,
+            None => KEEPER.remove(deps.storage),
+        }
+
+        let res = Response::new()
+            .add_attribute("action", "update_keeper")
+            .add_attribute("sender", info.sender)
+            .add_attribute("keeper", keeper.map(String::from).unwrap_or_default());
+        Ok(res)
+    }
+
+    pub fn update_guardian(deps: DepsMut, info: MessageInfo, guardian: Option<String>) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+
+        let guardian = guardian.map(|guardian| deps.api.addr_validate(&guardian)).transpose()?;
+        match &guardian {
+            Some(guardian) => GUARDIAN.save(deps.storage, guardian)?,
+            None => GUARDIAN.remove(deps.storage),
+        }
+
+        let res = Response::new()
+            .add_attribute("action", "update_guardian")
+            .add_attribute("sender", info.sender)
+            .add_attribute("guardian", guardian.map(String::from).unwrap_or_default());
+        Ok(res)
+    }
+
+    pub fn set_paused(
+        deps: DepsMut,
+        info: MessageInfo,
+        stake: Option<bool>,
+        unstake: Option<bool>,
+        swap: Option<bool>,
+    ) -> Result<Response, ContractError> {
+        if GUARDIAN.may_load(deps.storage)?.as_ref() != Some(&info.sender) {
+            assert_owner(deps.as_ref(), &info.sender)?;
+        }
+
+        let mut paused : PauseFlags = PAUSED.load(deps.storage)?;
+        paused.stake = stake.unwrap_or(paused.stake);
+        paused.unstake = unstake.unwrap_or(paused.unstake);
+        paused.swap = swap.unwrap_or(paused.swap);
+        PAUSED.save(deps.storage, &paused)?;
+
+        let res = Response::new()
+            .add_attribute("action", "set_paused")
+            .add_attribute("sender", info.sender)
+            .add_attribute("stake", paused.stake.to_string())
+            .add_attribute("unstake", paused.unstake.to_string())
+            .add_attribute("swap", paused.swap.to_string());
+        Ok(res)
+    }
+
+    pub fn propose_new_owner(deps: DepsMut, info: MessageInfo, new_owner: String) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+
+        let new_owner = deps.api.addr_validate(&new_owner)?;
+        PENDING_OWNER.save(deps.storage, &new_owner)?;
+
+        let res = Response::new()
+            .add_attribute("action", "propose_new_owner")
+            .add_attribute("sender", info.sender)
+            .add_attribute("pending_owner", new_owner);
+        Ok(res)
+    }
+
+    pub fn accept_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        if PENDING_OWNER.may_load(deps.storage)?.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        let previous_owner = OWNER.may_load(deps.storage)?;
+        OWNER.save(deps.storage, &info.sender)?;
+        PENDING_OWNER.remove(deps.storage);
+
+        let res = Response::new()
+            .add_attribute("action", "accept_ownership")
+            .add_attribute("previous_owner", previous_owner.map(String::from).unwrap_or_default())
+            .add_attribute("owner", info.sender);
+        Ok(res)
+    }
+
+    pub fn renounce_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+        assert_owner(deps.as_ref(), &info.sender)?;
+        OWNER.remove(deps.storage);
+        PENDING_OWNER.remove(deps.storage);
+
+        let res = Response::new()
+            .add_attribute("action", "renounce_ownership")
+            .add_attribute("sender", info.sender);
+        Ok(res)
+    }
+
+    pub fn rebalance(mut deps: DepsMut, _env: Env, info: MessageInfo, token_ids: Vec<String>) -> Result<Response, ContractError> {
+        assert_owner_or_keeper(deps.as_ref(), &info.sender)?;
+
+        // redelegating withdraws rewards on both validators, so settle first
+        let (_, withdraw_msgs) = update_reward_index(deps.branch(), &_env)?;
+        let denom : String = DENOM.load(deps.storage)?;
+        let active_validators : Vec<Validator> = deps.querier.query_all_validators()?;
+
+        let mut res = Response::new()
+            .add_attribute("action", "rebalance")
+            .add_attribute("sender", info.sender)
+            .add_messages(withdraw_msgs);
+
+        for token_id in token_ids {
+            let mut position : Position = if token_id == SHARE_POOL_ID {
+                load_share_pool(deps.storage, &_env)?.ok_or_else(|| ContractError::PositionNotFound { token_id: token_id.clone() })?
+            } else {
+                load_position(deps.storage, &_env, &token_id)?
+            };
+
+            let mut delegations : Vec<PositionDelegation> = vec![];
+            let mut moved = false;
+            for delegation in position.delegations.clone() {
+                if is_healthy(deps.as_ref(), &active_validators, &delegation.validator)? {
+                    delegations.push(delegation);
+                    continue;
+                }
+
+                // move the stake to validators picked by the configured strategy
+                for target in select_redelegation_targets(deps.branch(), delegation.amount)? {
+                    res = res
+                        .add_message(StakingMsg::Redelegate {
+                            src_validator: delegation.validator.clone(),
+                            dst_validator: target.validator.clone(),
+                            amount: Coin {
+                                denom: denom.clone(),
+                                amount: target.amount,
+                            },
+                        })
+                        .add_event(Event::new("redelegate")
+                            .add_attribute("token_id", token_id.clone())
+                            .add_attribute("src_validator", delegation.validator.clone())
+                            .add_attribute("dst_validator", target.validator.clone())
+                            .add_attribute("amount", target.amount.to_string()));
+                    delegations.push(target);
+                }
+                moved = true;
+            }
+
+            if moved {
+                sub_validator_totals(deps.storage, &position.delegations)?;
+                position.delegations = merge_delegations(delegations);
+                add_validator_totals(deps.storage, &position.delegations)?;
+                if token_id == SHARE_POOL_ID {
+                    SHARE_POOL.save(deps.storage, &position)?;
+                } else {
+                    tokens().save(deps.storage, token_id, &position)?;
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn swap(deps : DepsMut, _env: Env, info: MessageInfo, denom_to: String) -> Result<Response, ContractError> {
+
+        let info_clone = info.clone();
+
+        // get fund
+        let payment = info_clone // fund should be single
+            .funds
+            .iter()
+            .find(|_coin| true)
+            .ok_or(ContractError::InvalidDenom {})?;
+        let fund : Uint128 = payment.amount;
+
+        // get fee
+        let fee : Uint128 = calc_fee(&FEE.load(deps.storage)?.swap, &payment.denom, fund);
+        
+        // get denom_to
+        if payment.denom == denom_to {
+            return Err(ContractError::SameDenomSwap {});
+        }
+
+        // error if fund is equal or smaller than fee
+        if fund <= fee {
+            return Err(ContractError::InsufficientFunds { fund, fee });
+        }
+
+        let amount_to_swap = fund - fee;
+        collect_fee(deps.storage, &payment.denom, fee)?;
+
+        // query ubrown balance, fees in the treasury and coins owed to users
+        // are not part of the reserve
+        let contract_address : String = _env.contract.address.into_string();
+        let res = deps.querier.query_balance(contract_address, denom_to.clone())?;
+        let treasury : Uint128 = TREASURY.may_load(deps.storage, &denom_to)?.unwrap_or_default();
+        let liabilities : Uint128 = LIABILITIES.may_load(deps.storage, &denom_to)?.unwrap_or_default();
+        let denom_to_balance = res.clone().amount.saturating_sub(treasury).saturating_sub(liabilities);
+        // error if swap amount requested is greater than denom_to_balance
+        if denom_to_balance < amount_to_swap {
+            return Err(ContractError::InsufficientReserve {
+                denom: denom_to,
+                available: denom_to_balance,
+            });
+        }
+
+        // send to sender
+        let res = Response::new()
+            .add_attribute("action", "swap")
+            .add_attribute("sender", info.clone().sender)
+            .add_attribute("amount", amount_to_swap.clone().to_string())
+            .add_attribute("denom_to", denom_to.clone())
+            .add_message(BankMsg::Send {
+                to_address: info.clone().sender.into_string(),
+                amount: vec![Coin {
+                    denom: denom_to.clone(),
+                    amount: amount_to_swap,
+                }]
+            });
+        Ok(res)
+    }
+
+}
+
+
+// split the staking denom funds into (denom, fee, amount to stake)
+fn parse_stake_funds(deps: Deps, info: &MessageInfo) -> Result<(String, Uint128, Uint128), ContractError> {
+
+    // get denom
+    let denom : String = DENOM.load(deps.storage)?;
+
+    // get fund
+    let payment = info
+        .funds
+        .iter()
+        .find(|coin| coin.denom == denom)
+        .ok_or(ContractError::InvalidDenom {})?;
+    let fund : Uint128 = payment.amount;
+
+    // get fee
+    let fee : Uint128 = calc_fee(&FEE.load(deps.storage)?.stake, &denom, fund);
+
+    // error if fund is equal or smaller than fee
+    if fund <= fee {
+        return Err(ContractError::InsufficientFunds { fund, fee });
+    }
+
+    Ok((denom, fee, fund - fee))
+}
+
+fn config_event(key: &str, old: String, new: String) -> Event {
+    Event::new("config_changed")
+        .add_attribute("key", key)
+        .add_attribute("old", old)
+        .add_attribute("new", new)
+}
+
+fn assert_owner(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if OWNER.may_load(deps.storage)?.as_ref() != Some(sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+fn assert_owner_or_keeper(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    if KEEPER.may_load(deps.storage)?.as_ref() == Some(sender) {
+        return Ok(());
+    }
+    assert_owner(deps, sender)
+}
+
+// fail when msg belongs to a paused operation
+fn assert_not_paused(deps: Deps, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    use ExecuteMsg::*;
+
+    let paused : PauseFlags = PAUSED.load(deps.storage)?;
+    let operation = match msg {
+        Stake { .. } | StakeMore { .. } if paused.stake => "stake",
+        Reward { .. } | Unbond { .. } | EmergencyUnbond { .. } | PartialUnbond { .. } | Redeem { .. }
+            if paused.unstake => "unstake",
+        Swap { .. } if paused.swap => "swap",
+        _ => return Ok(()),
+    };
+    Err(ContractError::Paused { operation: operation.to_string() })
+}
+
+// check the sender owns the position or may send its nft, and return the
+// owner; approvals and operators are only known to the callee
+fn assert_nft_owner(deps: Deps, token_id: &str, position: &Position, sender: &Addr) -> Result<Addr, ContractError> {
+    if position.owner != *sender {
+        let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+        let contract = CalleeContract { address: Addr::unchecked(address) };
+        if !contract.is_approved(token_id.to_string(), sender.to_string())? {
+            return Err(ContractError::Unauthorized {});
+        }
+    }
+    Ok(position.owner.clone())
+}
+
+// load a position brought up to date, see refresh_position
+fn load_position(storage: &mut dyn Storage, env: &Env, token_id: &str) -> Result<Position, ContractError> {
+    let mut position : Position = find_position(storage, token_id)?;
+    if refresh_position(storage, env, token_id, &mut position)? {
+        tokens().save(storage, token_id.to_string(), &position)?;
+    }
+    Ok(position)
+}
+
+// apply the slashes since the position was last touched and end an expired
+// lock, true when the position changed
+fn refresh_position(storage: &mut dyn Storage, env: &Env, token_id: &str, position: &mut Position) -> StdResult<bool> {
+    let reward_index : Decimal = REWARD_INDEX.load(storage)?;
+    let mut changed = apply_slashes(storage, token_id, position)?;
+    if let Some(weight) = end_expired_lock(position, reward_index, env.block.time) {
+        update_total_weight(storage, weight, position_weight(position, env.block.time))?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+fn find_position(storage: &dyn Storage, token_id: &str) -> Result<Position, ContractError> {
+    tokens()
+        .may_load(storage, token_id.to_string())?
+        .ok_or_else(|| ContractError::PositionNotFound { token_id: token_id.to_string() })
+}
+
+// the share pool brought up to date, see refresh_position
+fn load_share_pool(storage: &mut dyn Storage, env: &Env) -> StdResult<Option<Position>> {
+    let mut pool : Position = match SHARE_POOL.may_load(storage)? {
+        Some(pool) => pool,
+        None => return Ok(None),
+    };
+    if refresh_position(storage, env, SHARE_POOL_ID, &mut pool)? {
+        SHARE_POOL.save(storage, &pool)?;
+    }
+    Ok(Some(pool))
+}
+
+fn to_token_id(seq: u64) -> String {
+    format!("cw721_{}", seq)
+}
+
+// next free position sequence; ids minted from block heights before the
+// sequence existed are skipped
+fn next_position_seq(storage: &dyn Storage) -> StdResult<u64> {
+    let mut seq : u64 = POSITION_SEQ.may_load(storage)?.unwrap_or(1);
+    while tokens().has(storage, to_token_id(seq)) {
+        seq += 1;
+    }
+    Ok(seq)
+}
+
+// take the next free token id, so stakes in one block get different ids
+fn reserve_token_id(storage: &mut dyn Storage) -> StdResult<String> {
+    let seq : u64 = next_position_seq(storage)?;
+    POSITION_SEQ.save(storage, &(seq + 1))?;
+    Ok(to_token_id(seq))
+}
+
+// a validator is healthy while it is known and in the active set; jailed and
+// tombstoned validators drop out of the active set
+fn is_healthy(deps: Deps, active_validators: &[Validator], validator: &str) -> StdResult<bool> {
+    if deps.querier.query_validator(validator)?.is_none() {
+        return Ok(false);
+    }
+    Ok(active_validators.iter().any(|active| active.address == validator))
+}
+
+// move each amount out of the position into a new position of the same owner
+// and return (owner, new token ids); the caller mints their nfts
+fn split_position(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    token_id: &str,
+    amounts: Vec<Uint128>,
+    reward_index: Decimal,
+) -> Result<(Addr, Vec<String>), ContractError> {
+    let mut position : Position = load_position(deps.storage, env, token_id)?;
+    let owner : Addr = assert_nft_owner(deps.as_ref(), token_id, &position, sender)?;
+
+    // every portion has to be bonded and the original keeps a part
+    let total = amounts
+        .iter()
+        .try_fold(Uint128::zero(), |total, amount| total.checked_add(*amount))
+        ?;
+    if amounts.iter().any(|amount| amount.is_zero()) || total >= position.principal {
+        return Err(ContractError::InvalidAmount { available: position.principal });
+    }
+
+    // the original keeps its accrued reward, new positions start from the current index
+    settle_position(&mut position, reward_index, env.block.time);
+
+    // split positions keep the lock, the weights only differ by rounding
+    let weight : Uint128 = position_weight(&position, env.block.time);
+    let mut new_weight = Uint128::zero();
+    let mut new_token_ids : Vec<String> = vec![];
+    for amount in amounts {
+        // delegations only move between positions, nothing is redelegated
+        let (remaining, taken) = take_delegations(&position.delegations, amount)?;
+        position.delegations = remaining;
+        position.principal -= amount;
+
+        let new_token_id : String = reserve_token_id(deps.storage)?;
+        let new_position = Position {
+            owner: owner.clone(),
+            principal: amount,
+            denom: position.denom.clone(),
+            delegations: taken,
+            start_height: position.start_height,
+            start_time: position.start_time,
+            reward_index,
+            accrued_reward: Uint128::zero(),
+            compounding: position.compounding,
+            lock: position.lock.clone(),
+            slash_seq: position.slash_seq,
+        };
+        new_weight += position_weight(&new_position, env.block.time);
+        tokens().save(deps.storage, new_token_id.clone(), &new_position)?;
+        new_token_ids.push(new_token_id);
+    }
+    tokens().save(deps.storage, token_id.to_string(), &position)?;
+    update_total_weight(deps.storage, weight, new_weight + position_weight(&position, env.block.time))?;
+
+    Ok((owner, new_token_ids))
+}
+
+// fold the positions in token_ids into the position into and return it; the
+// caller burns their nfts
+fn merge_positions(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    token_ids: &[String],
+    into: &str,
+    reward_index: Decimal,
+) -> Result<Position, ContractError> {
+    let mut position : Position = load_position(deps.storage, env, into)?;
+    let owner : Addr = assert_nft_owner(deps.as_ref(), into, &position, sender)?;
+
+    // settle every position so the accrued rewards can be added up
+    settle_position(&mut position, reward_index, env.block.time);
+    let mut weight : Uint128 = position_weight(&position, env.block.time);
+
+    for token_id in token_ids {
+        let mut merged : Position = load_position(deps.storage, env, token_id)?;
+
+        // value must not move between owners
+        if assert_nft_owner(deps.as_ref(), token_id, &merged, sender)? != owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        settle_position(&mut merged, reward_index, env.block.time);
+
+        // locks of one tier merge into the one expiring last
+        if lock_multiplier(&merged, env.block.time) != lock_multiplier(&position, env.block.time) {
+            return Err(ContractError::InvalidMerge(format!("{} has a different lock tier", token_id)));
+        }
+        position.lock = match (position.lock, merged.lock.clone()) {
+            (Some(lock), Some(merged_lock)) if merged_lock.expires_at > lock.expires_at => Some(merged_lock),
+            (lock, merged_lock) => lock.or(merged_lock),
+        };
+        weight += position_weight(&merged, env.block.time);
+
+        // positions can hold several validators, so delegations are
+        // combined without redelegating
+        position.principal = position.principal.checked_add(merged.principal)?;
+        position.accrued_reward = position.accrued_reward.checked_add(merged.accrued_reward)?;
+        position.delegations = merge_delegations([position.delegations, merged.delegations].concat());
+        tokens().remove(deps.storage, token_id.clone())?;
+    }
+    tokens().save(deps.storage, into.to_string(), &position)?;
+    update_total_weight(deps.storage, weight, position_weight(&position, env.block.time))?;
+
+    Ok(position)
+}
+
+// combine delegations to the same validator
+fn merge_delegations(delegations: Vec<PositionDelegation>) -> Vec<PositionDelegation> {
+    let mut merged : Vec<PositionDelegation> = vec![];
+    for delegation in delegations {
+        match merged.iter_mut().find(|d| d.validator == delegation.validator) {
+            Some(existing) => existing.amount += delegation.amount,
+            None => merged.push(delegation),
+        }
+    }
+    merged
+}
+
+// take amount out of delegations in proportion to their size and return the
+// (remaining, taken) delegations; amount must not exceed the delegated total
+fn take_delegations(
+    delegations: &[PositionDelegation],
+    amount: Uint128,
+) -> StdResult<(Vec<PositionDelegation>, Vec<PositionDelegation>)> {
+    let total : Uint128 = delegations.iter().map(|delegation| delegation.amount).sum();
+    let mut taken : Vec<PositionDelegation> = delegations
+        .iter()
+        .map(|delegation| PositionDelegation {
+            validator: delegation.validator.clone(),
+            amount: amount.multiply_ratio(delegation.amount, total),
+        })
+        .collect();
+
+    // hand the rounding remainder to delegations that still have room
+    let mut left : Uint128 = amount.checked_sub(taken.iter().map(|t| t.amount).sum())?;
+    for (take, delegation) in taken.iter_mut().zip(delegations) {
+        let extra = left.min(delegation.amount - take.amount);
+        take.amount += extra;
+        left -= extra;
+    }
+
+    let remaining : Vec<PositionDelegation> = delegations
+        .iter()
+        .zip(&taken)
+        .map(|(delegation, take)| PositionDelegation {
+            validator: delegation.validator.clone(),
+            amount: delegation.amount - take.amount,
+        })
+        .filter(|delegation| !delegation.amount.is_zero())
+        .collect();
+    taken.retain(|take| !take.amount.is_zero());
+    Ok((remaining, taken))
+}
+
+fn push_claim(storage: &mut dyn Storage, address: &Addr, claim: Claim) -> StdResult<()> {
+    add_liability(storage, &claim.denom, claim.amount)?;
+    CLAIMS.update(
+        storage,
+        address,
+        |claims: Option<Vec<Claim>>| -> StdResult<_> {
+            let mut claims = claims.unwrap_or_default();
+            claims.push(claim);
+            Ok(claims)
+        },
+    )?;
+    Ok(())
+}
+
+fn add_liability(storage: &mut dyn Storage, denom: &str, amount: Uint128) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    LIABILITIES.update(storage, denom, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+fn sub_liability(storage: &mut dyn Storage, denom: &str, amount: Uint128) -> StdResult<()> {
+    let total = LIABILITIES
+        .may_load(storage, denom)?
+        .unwrap_or_default()
+        .checked_sub(amount)?;
+    if total.is_zero() {
+        LIABILITIES.remove(storage, denom);
+    } else {
+        LIABILITIES.save(storage, denom, &total)?;
+    }
+    Ok(())
+}
+
+// VALIDATOR_TOTALS follows every change to position delegations
+fn add_validator_totals(storage: &mut dyn Storage, delegations: &[PositionDelegation]) -> StdResult<()> {
+    for delegation in delegations {
+        VALIDATOR_TOTALS.update(
+            storage,
+            &delegation.validator,
+            |total: Option<Uint128>| -> StdResult<_> {
+                Ok(total.unwrap_or_default().checked_add(delegation.amount)?)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn sub_validator_totals(storage: &mut dyn Storage, delegations: &[PositionDelegation]) -> StdResult<()> {
+    for delegation in delegations {
+        let total = VALIDATOR_TOTALS
+            .may_load(storage, &delegation.validator)?
+            .unwrap_or_default()
+            .checked_sub(delegation.amount)?;
+        if total.is_zero() {
+            VALIDATOR_TOTALS.remove(storage, &delegation.validator);
+        } else {
+            VALIDATOR_TOTALS.save(storage, &delegation.validator, &total)?;
+        }
+    }
+    Ok(())
+}
+
+// reward index including the rewards not withdrawn yet, the validators
+// holding them and the delegation rewards added to the index
+fn current_reward_index(deps: Deps, env: &Env) -> StdResult<(Decimal, Vec<String>, Uint128)> {
+    let denom : String = DENOM.load(deps.storage)?;
+    let reward_index : Decimal = REWARD_INDEX.load(deps.storage)?;
+    let total_weight : Uint128 = TOTAL_WEIGHT.load(deps.storage)?;
+
+    let mut accrued = Uint128::zero();
+    let mut validators : Vec<String> = vec![];
+    for delegation in deps.querier.query_all_delegations(&env.contract.address)? {
+        let full_delegation = deps
+            .querier
+            .query_delegation(&env.contract.address, &delegation.validator)?;
+        let reward : Uint128 = full_delegation
+            .map(|full| {
+                full.accumulated_rewards
+                    .iter()
+                    .filter(|coin| coin.denom == denom)
+                    .map(|coin| coin.amount)
+                    .sum()
+            })
+            .unwrap_or_default();
+        if !reward.is_zero() {
+            accrued = accrued.checked_add(reward)?;
+            validators.push(delegation.validator);
+        }
+    }
+    let rewards : Uint128 = accrued;
+
+    // unbonded penalties for the stakers are shared like rewards
+    for penalty in PENDING_PENALTIES.load(deps.storage)? {
+        if penalty.destination == (PenaltyDestination::Stakers {})
+            && penalty.denom == denom
+            && penalty.release_at <= env.block.time
+        {
+            accrued = accrued.checked_add(penalty.amount)?;
+        }
+    }
+
+    // rewards can't be attributed while nothing is bonded
+    if total_weight.is_zero() {
+        return Ok((reward_index, validators, Uint128::zero()));
+    }
+    Ok((reward_index + Decimal::from_ratio(accrued, total_weight), validators, rewards))
+}
+
+// withdraw delegation rewards and add them to the reward index; slashes are
+// applied first, so positions must be loaded after this
+fn update_reward_index(mut deps: DepsMut, env: &Env) -> StdResult<(Decimal, Vec<DistributionMsg>)> {
+    reconcile_slashes(deps.branch(), env)?;
+    end_expired_locks(deps.storage, env)?;
+    let (reward_index, validators, rewards) = current_reward_index(deps.as_ref(), env)?;
+    REWARD_INDEX.save(deps.storage, &reward_index)?;
+    // withdrawn rewards belong to the positions until they are paid out or compounded
+    let denom : String = DENOM.load(deps.storage)?;
+    add_liability(deps.storage, &denom, rewards)?;
+    release_penalties(deps.storage, env)?;
+
+    // keep the previous checkpoint, so the apr always spans at least a window
+    let mut checkpoints : Vec<RewardCheckpoint> = REWARD_CHECKPOINTS.load(deps.storage)?;
+    let latest = checkpoints.last().map_or(env.block.time, |checkpoint| checkpoint.time);
+    if env.block.time >= latest.plus_seconds(APR_WINDOW) {
+        checkpoints.push(RewardCheckpoint {
+            time: env.block.time,
+            index: reward_index,
+        });
+        if checkpoints.len() > 2 {
+            checkpoints.remove(0);
+        }
+        REWARD_CHECKPOINTS.save(deps.storage, &checkpoints)?;
+    }
+
+    let withdraw_msgs = validators
+        .into_iter()
+        .map(|validator| DistributionMsg::WithdrawDelegatorReward { validator })
+        .collect();
+    Ok((reward_index, withdraw_msgs))
+}
+
+// end the locks expired since the last update before the new rewards are
+// added, so they aren't shared on the boost; the ones past the limit are
+// ended by the next update or when the position is touched
+fn end_expired_locks(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    // every id with an expiry up to now sorts before (now + 1, "")
+    let end = Bound::exclusive((env.block.time.seconds() + 1, String::new()));
+    let expired : Vec<(String, Position)> = tokens()
+        .idx
+        .lock_expiry
+        .range(storage, None, Some(end), Order::Ascending)
+        .take(EXPIRED_LOCK_LIMIT)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (token_id, mut position) in expired {
+        refresh_position(storage, env, &token_id, &mut position)?;
+        tokens().save(storage, token_id, &position)?;
+    }
+    Ok(())
+}
+
+// compare what is recorded on each validator with what is actually delegated
+// and record a slash event for each validator that lost part of it; the lost
+// amount stops earning right away, the positions are scaled down lazily, see
+// apply_slashes
+fn reconcile_slashes(deps: DepsMut, env: &Env) -> StdResult<()> {
+    let denom : String = DENOM.load(deps.storage)?;
+    let recorded : Vec<(String, Uint128)> = VALIDATOR_TOTALS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    if recorded.is_empty() {
+        return Ok(());
+    }
+
+    let delegations = deps.querier.query_all_delegations(&env.contract.address)?;
+    let reward_index : Decimal = REWARD_INDEX.load(deps.storage)?;
+    let mut id : u64 = SLASH_EVENT_SEQ.may_load(deps.storage)?.unwrap_or_default();
+    for (validator, total) in recorded {
+        let actual : Uint128 = delegations
+            .iter()
+            .find(|delegation| delegation.validator == validator && delegation.amount.denom == denom)
+            .map(|delegation| delegation.amount.amount)
+            .unwrap_or_default();
+        if actual + SLASH_TOLERANCE >= total {
+            continue;
+        }
+
+        if actual.is_zero() {
+            VALIDATOR_TOTALS.remove(deps.storage, &validator);
+        } else {
+            VALIDATOR_TOTALS.save(deps.storage, &validator, &actual)?;
+        }
+        let amount : Uint128 = total - actual;
+        update_total_weight(deps.storage, amount, Uint128::zero())?;
+        let unapplied : Uint128 = UNAPPLIED_SLASH_WEIGHT.may_load(deps.storage)?.unwrap_or_default();
+        UNAPPLIED_SLASH_WEIGHT.save(deps.storage, &unapplied.checked_add(amount)?)?;
+        SLASH_EVENTS.save(deps.storage, id, &SlashEvent {
+            validator,
+            height: env.block.height,
+            time: env.block.time,
+            ratio: Decimal::from_ratio(actual, total),
+            amount,
+            reward_index,
+        })?;
+        id += 1;
+        SLASH_EVENT_SEQ.save(deps.storage, &id)?;
+    }
+    Ok(())
+}
+
+// scale the delegations of a position by the slash events recorded since it
+// was last scaled, settling the reward up to each event on the amount before
+// it, and return the loss per slash event id
+fn scale_by_slashes(storage: &dyn Storage, position: &mut Position) -> StdResult<Vec<(u64, Uint128)>> {
+    let seq : u64 = SLASH_EVENT_SEQ.may_load(storage)?.unwrap_or_default();
+    if position.slash_seq >= seq {
+        return Ok(vec![]);
+    }
+
+    let events : Vec<(u64, SlashEvent)> = SLASH_EVENTS
+        .range(storage, Some(Bound::inclusive(position.slash_seq)), None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut losses : Vec<(u64, Uint128)> = vec![];
+    for (id, event) in events {
+        let i : usize = match position.delegations.iter().position(|delegation| delegation.validator == event.validator) {
+            Some(i) => i,
+            None => continue,
+        };
+        let kept : Uint128 = position.delegations[i].amount * event.ratio;
+        let loss : Uint128 = position.delegations[i].amount - kept;
+        if loss.is_zero() {
+            continue;
+        }
+        if event.reward_index > position.reward_index {
+            position.accrued_reward += calc_reward(counted_weight(position), position.reward_index, event.reward_index);
+            position.reward_index = event.reward_index;
+        }
+        position.delegations[i].amount = kept;
+        position.principal -= loss;
+        losses.push((id, loss));
+    }
+    position.delegations.retain(|delegation| !delegation.amount.is_zero());
+    position.slash_seq = seq;
+    Ok(losses)
+}
+
+// apply the slashes a position hasn't seen yet, recording its loss to each;
+// the loss itself already left TOTAL_WEIGHT with the slash event, only the
+// boost on it is still counted
+fn apply_slashes(storage: &mut dyn Storage, token_id: &str, position: &mut Position) -> StdResult<bool> {
+    let slash_seq : u64 = position.slash_seq;
+    let weight : Uint128 = counted_weight(position);
+    let losses = scale_by_slashes(storage, position)?;
+    if position.slash_seq == slash_seq {
+        return Ok(false);
+    }
+
+    let lost : Uint128 = losses.iter().map(|(_, loss)| *loss).sum();
+    let unapplied : Uint128 = UNAPPLIED_SLASH_WEIGHT.may_load(storage)?.unwrap_or_default();
+    let taken : Uint128 = lost.min(unapplied);
+    UNAPPLIED_SLASH_WEIGHT.save(storage, &(unapplied - taken))?;
+    update_total_weight(storage, weight - taken, counted_weight(position))?;
+    for (id, loss) in losses {
+        POSITION_SLASHES.save(storage, (token_id, id), &loss)?;
+    }
+    Ok(true)
+}
+
+// move a fee into the treasury and count it in the fees collected per denom
+fn collect_fee(storage: &mut dyn Storage, denom: &str, fee: Uint128) -> StdResult<()> {
+    if fee.is_zero() {
+        return Ok(());
+    }
+    FEES_COLLECTED.update(storage, denom, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(fee)?)
+    })?;
+    TREASURY.update(storage, denom, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default().checked_add(fee)?)
+    })?;
+    Ok(())
+}
+
+// drop unbonded penalties from the pending list, staker penalties were just
+// added to the reward index and treasury penalties go to the treasury
+fn release_penalties(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    // staker penalties stay pending while there is no one to share them with
+    let shared : bool = !TOTAL_WEIGHT.load(storage)?.is_zero();
+    let denom : String = DENOM.load(storage)?;
+    let (released, pending) : (Vec<PendingPenalty>, Vec<PendingPenalty>) = PENDING_PENALTIES
+        .load(storage)?
+        .into_iter()
+        .partition(|penalty| {
+            penalty.release_at <= env.block.time
+                && match penalty.destination {
+                    PenaltyDestination::Stakers {} => shared && penalty.denom == denom,
+                    PenaltyDestination::Treasury {} => true,
+                }
+        });
+    if released.is_empty() {
+        return Ok(());
+    }
+
+    for penalty in released {
+        if penalty.destination == (PenaltyDestination::Treasury {}) {
+            sub_liability(storage, &penalty.denom, penalty.amount)?;
+            TREASURY.update(storage, &penalty.denom, |balance| -> StdResult<_> {
+                Ok(balance.unwrap_or_default().checked_add(penalty.amount)?)
+            })?;
+        }
+    }
+    PENDING_PENALTIES.save(storage, &pending)
+}
+
+// penalty for leaving a locked position now, decaying linearly from
+// EARLY_EXIT_PENALTY at the start of the lock to zero at expiry
+fn calc_early_exit_penalty(deps: Deps, env: &Env, position: &Position) -> StdResult<Uint128> {
+    let lock = match &position.lock {
+        Some(lock) if lock.duration > 0 && env.block.time < lock.expires_at => lock,
+        _ => return Ok(Uint128::zero()),
+    };
+    let early_exit_penalty : Decimal = EARLY_EXIT_PENALTY.load(deps.storage)?;
+    let remaining : u64 = lock.expires_at.seconds() - env.block.time.seconds();
+    Ok(position.principal * (early_exit_penalty * Decimal::from_ratio(remaining, lock.duration)))
+}
+
+// re-delegate the accrued reward of a compounding position, minus the
+// performance fee, and return (new delegations, compounded, fee); None when
+// nothing is left to compound, the caller saves the position
+fn compound_position(
+    storage: &mut dyn Storage,
+    env: &Env,
+    token_id: &str,
+    position: &mut Position,
+    reward_index: Decimal,
+    performance_fee: &OperationFee,
+) -> StdResult<Option<(Vec<PositionDelegation>, Uint128, Uint128)>> {
+    settle_position(position, reward_index, env.block.time);
+    let denom : String = DENOM.load(storage)?;
+    let reward : Uint128 = position.accrued_reward;
+    let fee : Uint128 = calc_fee(performance_fee, &denom, reward).min(reward);
+    let compounded : Uint128 = reward - fee;
+    // a fully slashed position has no delegations to add the reward to, it
+    // stays accrued until the position is unbonded
+    if compounded.is_zero() || position.delegations.is_empty() {
+        return Ok(None);
+    }
+    sub_liability(storage, &denom, reward)?;
+    collect_fee(storage, &denom, fee)?;
+
+    // re-delegate like the position's current delegations
+    let weights : Vec<(String, u128)> = position
+        .delegations
+        .iter()
+        .map(|delegation| (delegation.validator.clone(), delegation.amount.u128()))
+        .collect();
+    let delegations = split_by_weight(weights, compounded)?;
+    let weight : Uint128 = position_weight(position, env.block.time);
+    position.delegations = merge_delegations([position.delegations.clone(), delegations.clone()].concat());
+    position.principal = position.principal.checked_add(compounded)?;
+    position.accrued_reward = Uint128::zero();
+
+    update_total_weight(storage, weight, position_weight(position, env.block.time))?;
+    COMPOUND_HISTORY.update(
+        storage,
+        (token_id, env.block.height),
+        |record: Option<CompoundRecord>| -> StdResult<_> {
+            let mut record = record.unwrap_or(CompoundRecord {
+                height: env.block.height,
+                time: env.block.time,
+                reward: Uint128::zero(),
+                fee: Uint128::zero(),
+                compounded: Uint128::zero(),
+            });
+            record.reward += reward;
+            record.fee += fee;
+            record.compounded += compounded;
+            Ok(record)
+        },
+    )?;
+    Ok(Some((delegations, compounded, fee)))
+}
+
+fn load_share_token(deps: Deps) -> Result<ShareTokenContract, ContractError> {
+    let address = SHARE_TOKEN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::LiquidStakingDisabled {})?;
+    Ok(ShareTokenContract { address })
+}
+
+fn share_supply(share_token: &ShareTokenContract) -> StdResult<Uint128> {
+    let token_info : TokenInfoResponse = from_binary(&share_token.token_info()?)?;
+    Ok(token_info.total_supply)
+}
+
+// compound the share pool, so its amount is the whole value behind the shares,
+// and return it with the messages delegating the compounded reward
+fn compound_share_pool(
+    storage: &mut dyn Storage,
+    env: &Env,
+    reward_index: Decimal,
+) -> StdResult<(Position, Vec<StakingMsg>)> {
+    let denom : String = DENOM.load(storage)?;
+    let slash_seq : u64 = SLASH_EVENT_SEQ.may_load(storage)?.unwrap_or_default();
+    let mut pool : Position = load_share_pool(storage, env)?
+        .unwrap_or(Position {
+            owner: env.contract.address.clone(),
+            principal: Uint128::zero(),
+            denom: denom.clone(),
+            delegations: vec![],
+            start_height: env.block.height,
+            start_time: env.block.time,
+            reward_index,
+            accrued_reward: Uint128::zero(),
+            compounding: true,
+            lock: None,
+            slash_seq,
+        });
+
+    let performance_fee : OperationFee = FEE.load(storage)?.performance;
+    let compounded = compound_position(storage, env, SHARE_POOL_ID, &mut pool, reward_index, &performance_fee)?;
+    let delegations = match compounded {
+        Some((delegations, _, _)) => delegations,
+        None => return Ok((pool, vec![])),
+    };
+
+    SHARE_POOL.save(storage, &pool)?;
+    add_validator_totals(storage, &delegations)?;
+
+    let msgs = delegations
+        .into_iter()
+        .map(|delegation| StakingMsg::Delegate {
+            validator: delegation.validator,
+            amount: Coin {
+                denom: denom.clone(),
+                amount: delegation.amount,
+            },
+        })
+        .collect();
+    Ok((pool, msgs))
+}
+
+// shares minted for an amount staked into the share pool, 1:1 while nothing
+// is bonded; dust worth less than a share is rejected
+fn shares_for_amount(amount: Uint128, supply: Uint128, bonded: Uint128) -> Result<Uint128, ContractError> {
+    let shares : Uint128 = if supply.is_zero() || bonded.is_zero() {
+        amount
+    } else {
+        amount.multiply_ratio(supply, bonded)
+    };
+    if shares.is_zero() {
+        return Err(ContractError::InvalidShareAmount { supply });
+    }
+    Ok(shares)
+}
+
+// pro rata part of the share pool the shares redeem for
+fn amount_for_shares(shares: Uint128, supply: Uint128, bonded: Uint128) -> Result<Uint128, ContractError> {
+    if shares.is_zero() || shares > supply {
+        return Err(ContractError::InvalidShareAmount { supply });
+    }
+    let amount : Uint128 = bonded.multiply_ratio(shares, supply);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidShareAmount { supply });
+    }
+    Ok(amount)
+}
+
+// move the reward earned so far into accrued_reward, so the amount can change
+fn settle_position(position: &mut Position, reward_index: Decimal, time: Timestamp) {
+    position.accrued_reward += calc_reward(position_weight(position, time), position.reward_index, reward_index);
+    position.reward_index = reward_index;
+}
+
+// TOTAL_WEIGHT keeps the boost of an expired lock until it is ended, see
+// end_expired_locks; settle the reward on that weight, drop the lock and
+// return the weight it had
+fn end_expired_lock(position: &mut Position, reward_index: Decimal, time: Timestamp) -> Option<Uint128> {
+    if !matches!(&position.lock, Some(lock) if time >= lock.expires_at) {
+        return None;
+    }
+    let weight : Uint128 = counted_weight(position);
+    position.accrued_reward += calc_reward(weight, position.reward_index, reward_index);
+    position.reward_index = reward_index;
+    position.lock = None;
+    Some(weight)
+}
+
+// weight a position is counted with in TOTAL_WEIGHT, boosted until the lock
+// is ended even if it already expired
+fn counted_weight(position: &Position) -> Uint128 {
+    position.principal * position.lock.as_ref().map_or(Decimal::one(), |lock| lock.multiplier)
+}
+
+// reward weight of a position, its amount boosted by the lock multiplier
+fn position_weight(position: &Position, time: Timestamp) -> Uint128 {
+    position.principal * lock_multiplier(position, time)
+}
+
+// the boost ends when the lock expires
+fn lock_multiplier(position: &Position, time: Timestamp) -> Decimal {
+    match &position.lock {
+        Some(lock) if time < lock.expires_at => lock.multiplier,
+        _ => Decimal::one(),
+    }
+}
+
+// replace a weight counted in TOTAL_WEIGHT with its new value
+fn update_total_weight(storage: &mut dyn Storage, before: Uint128, after: Uint128) -> StdResult<()> {
+    TOTAL_WEIGHT.update(storage, |total| -> StdResult<_> {
+        Ok(total.checked_sub(before)?.checked_add(after)?)
+    })?;
+    Ok(())
+}
+
+fn to_position_lock(deps: Deps, env: &Env, duration: u64) -> Result<PositionLock, ContractError> {
+    let tier : LockTier = LOCK_TIERS
+        .load(deps.storage)?
+        .into_iter()
+        .find(|tier| tier.duration == duration)
+        .ok_or(ContractError::LockTierNotFound { duration })?;
+    Ok(PositionLock {
+        duration,
+        multiplier: tier.multiplier,
+        expires_at: env.block.time.plus_seconds(duration),
+    })
+}
+
+fn assert_unlocked(env: &Env, position: &Position) -> Result<(), ContractError> {
+    match &position.lock {
+        Some(lock) if env.block.time < lock.expires_at => {
+            Err(ContractError::PositionLocked { expires_at: lock.expires_at })
+        }
+        _ => Ok(()),
+    }
+}
+
+// claims wait at least a second and at most MAX_UNBONDING_PERIOD
+fn validate_unbonding_period(unbonding_period: u64) -> Result<(), ContractError> {
+    if unbonding_period == 0 || unbonding_period > MAX_UNBONDING_PERIOD {
+        return Err(ContractError::InvalidConfig(format!(
+            "unbonding_period must be between 1 and {} seconds",
+            MAX_UNBONDING_PERIOD,
+        )));
+    }
+    Ok(())
+}
+
+// a lock must not earn less than no lock, and durations pick a single tier
+fn validate_lock_tiers(tiers: &[LockTier]) -> Result<(), ContractError> {
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.multiplier < Decimal::one() {
+            return Err(ContractError::InvalidLockTiers(format!(
+                "multiplier of {} is below 1",
+                tier.duration
+            )));
+        }
+        if tiers[..i].iter().any(|other| other.duration == tier.duration) {
+            return Err(ContractError::InvalidLockTiers(format!(
+                "duration {} is listed more than once",
+                tier.duration
+            )));
+        }
+    }
+    Ok(())
+}
+
+// reward earned by amount while the index moved from prev_index to reward_index
+fn calc_reward(amount: Uint128, prev_index: Decimal, reward_index: Decimal) -> Uint128 {
+    amount * (reward_index - prev_index)
+}
+
+pub fn try_mint(
+    deps: DepsMut,
+    msg: MintingMsg
+) -> Result<Response, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    let mint_res = contract.mint(msg.clone())?;
+    let res = Response::default()
+        .add_attributes(mint_res);
+
+    Ok(res)
+}
+
+pub fn try_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+    msg: TransferMsg,
+) -> Result<Response, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    let recipient : Addr = deps.api.addr_validate(&msg.recipient)?;
+    let transfer_res = contract.transfer_nft(info, msg.clone().recipient, msg.clone().token_id)?;
+    set_position_owner(deps.storage, &msg.token_id, recipient)?;
+    let res = Response::default()
+        .add_attributes(transfer_res);
+
+    Ok(res)
+}
+
+// move a position to the new owner of its nft, so the owner index follows transfers
+fn set_position_owner(storage: &mut dyn Storage, token_id: &str, owner: Addr) -> StdResult<Position> {
+    tokens().update(storage, token_id.to_string(), |position| -> StdResult<_> {
+        let mut position = position.ok_or_else(|| StdError::not_found("Position"))?;
+        position.owner = owner;
+        Ok(position)
+    })
+}
+
+#[callable_points]
+mod callable_points {
+    use super::*;
+
+    // the callee reports transfers of position nfts that didn't go through Transfer
+    #[callable_point]
+    fn nft_transferred(deps: DepsMut, _env: Env, token_id: String, recipient: String) -> StdResult<Vec<Attribute>> {
+        let caller : Addr = deps.api.get_caller_addr()?;
+        nft_transferred_by(deps, caller, token_id, recipient)
+    }
+}
+
+fn nft_transferred_by(deps: DepsMut, caller: Addr, token_id: String, recipient: String) -> StdResult<Vec<Attribute>> {
+    if caller != CALLEE_CONTRACT_ADDRESS.load(deps.storage)? {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    let recipient : Addr = deps.api.addr_validate(&recipient)?;
+    set_position_owner(deps.storage, &token_id, recipient.clone())?;
+    Ok(vec![
+        Attribute::new("action", "nft_transferred"),
+        Attribute::new("token_id", token_id),
+        Attribute::new("owner", recipient),
+    ])
+}
+
+pub fn try_burn(
+    deps: DepsMut,
+    msg: BurnMsg
+) -> Result<Response, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    let burn_res = contract.burn(msg.clone().token_id);
+    let res = Response::default()
+        .add_attributes(burn_res);
+    Ok(res)
+}
+
+// check the caller_address works
+pub fn try_call_caller_address_of(
+    deps: Deps,
+    _env: Env,
+    target: Addr,
+) -> Result<Response, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    let result_addr = contract.call_caller_address_of(target);
+    let res = Response::default().add_attribute(
+        "call_caller_address_is_as_expected",
+        (result_addr == address).to_string(),
+    );
+    Ok(res)
+}
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    match msg {
+        QueryMsg::GetOwnAddressViaCalleesGetCallerAddress {} => {
+            get_own_address_via_callees_get_caller_address(deps, env)
+        }
+        QueryMsg::Minter {} => {
+            minter(deps, env)
+        }
+        QueryMsg::OwnerOf { token_id, include_expired } => {
+            owner_of(deps, env, token_id, include_expired)
+        }
+        QueryMsg::Position { token_id } => {
+            position(deps, env, token_id)
+        }
+        QueryMsg::Positions { start_after, limit } => {
+            positions(deps, env, start_after, limit)
+        }
+        QueryMsg::PositionsByOwner { owner, start_after, limit } => {
+            positions_by_owner(deps, env, owner, start_after, limit)
+        }
+        QueryMsg::NextTokenId {} => {
+            next_token_id(deps, env)
+        }
+        QueryMsg::Claims { address } => {
+            claims(deps, env, address)
+        }
+        QueryMsg::ValidatorStrategy {} => {
+            validator_strategy(deps, env)
+        }
+        QueryMsg::ValidatorTotals {} => {
+            validator_totals(deps, env)
+        }
+        QueryMsg::CompoundHistory { token_id, start_after, limit } => {
+            compound_history(deps, env, token_id, start_after, limit)
+        }
+        QueryMsg::ShareRate {} => {
+            share_rate(deps, env)
+        }
+        QueryMsg::LockTiers {} => {
+            lock_tiers(deps, env)
+        }
+        QueryMsg::SlashEvents { token_id, start_after, limit } => {
+            slash_events(deps, env, token_id, start_after, limit)
+        }
+        QueryMsg::Config {} => {
+            config(deps, env)
+        }
+        QueryMsg::Ownership {} => {
+            ownership(deps, env)
+        }
+        QueryMsg::FeesCollected {} => {
+            fees_collected(deps, env)
+        }
+        QueryMsg::TreasuryBalance {} => {
+            treasury_balance(deps, env)
+        }
+        QueryMsg::Status {} => {
+            status(deps, env)
+        }
+    }
+}
+
+fn config(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    Ok(to_binary(&ConfigResponse {
+        owner: OWNER.may_load(deps.storage)?,
+        callee_contract_address: CALLEE_CONTRACT_ADDRESS.load(deps.storage)?,
+        share_token: SHARE_TOKEN.may_load(deps.storage)?,
+        fee: FEE.load(deps.storage)?,
+        denom: DENOM.load(deps.storage)?,
+        unbonding_period: UNBONDING_PERIOD.load(deps.storage)?,
+    })?)
+}
+
+fn ownership(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    Ok(to_binary(&OwnershipResponse {
+        owner: OWNER.may_load(deps.storage)?,
+        pending_owner: PENDING_OWNER.may_load(deps.storage)?,
+    })?)
+}
+
+fn status(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    Ok(to_binary(&StatusResponse {
+        paused: PAUSED.load(deps.storage)?,
+        guardian: GUARDIAN.may_load(deps.storage)?,
+    })?)
+}
+
+fn fees_collected(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let fees = FEES_COLLECTED
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&FeesCollectedResponse { fees })?)
+}
+
+fn treasury_balance(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let balance = TREASURY
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&TreasuryBalanceResponse { balance })?)
+}
+
+fn slash_events(
+    deps: Deps, _env: Env, token_id: Option<String>, start_after: Option<u64>, limit: Option<u32>,
+) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    // with a token_id only the slashes that hit the position, with its loss
+    let ids : Vec<(u64, Option<Uint128>)> = match &token_id {
+        Some(token_id) => POSITION_SLASHES
+            .prefix(token_id)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(id, loss)| (id, Some(loss))))
+            .collect::<StdResult<Vec<_>>>()?,
+        None => SLASH_EVENTS
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|id| (id, None)))
+            .collect::<StdResult<Vec<_>>>()?,
+    };
+
+    let events = ids
+        .into_iter()
+        .map(|(id, loss)| {
+            let event : SlashEvent = SLASH_EVENTS.load(deps.storage, id)?;
+            Ok(SlashEventInfo {
+                id,
+                validator: event.validator,
+                height: event.height,
+                time: event.time,
+                ratio: event.ratio,
+                amount: event.amount,
+                loss,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&SlashEventsResponse { events })?)
+}
+
+fn lock_tiers(
+    deps: Deps, env: Env,
+) -> Result<Binary, ContractError> {
+    // reward per unit of weight and year since the oldest checkpoint
+    let (reward_index, _, _) = current_reward_index(deps, &env)?;
+    let checkpoint : RewardCheckpoint = REWARD_CHECKPOINTS
+        .load(deps.storage)?
+        .into_iter()
+        .next()
+        .unwrap_or(RewardCheckpoint { time: env.block.time, index: reward_index });
+    let elapsed : u64 = env.block.time.seconds().saturating_sub(checkpoint.time.seconds());
+    let base_apr = if elapsed == 0 {
+        Decimal::zero()
+    } else {
+        (reward_index - checkpoint.index) * Decimal::from_ratio(SECONDS_PER_YEAR, elapsed)
+    };
+
+    let tiers = LOCK_TIERS
+        .load(deps.storage)?
+        .into_iter()
+        .map(|tier| LockTierInfo {
+            duration: tier.duration,
+            multiplier: tier.multiplier,
+            apr: base_apr * tier.multiplier,
+        })
+        .collect();
+    Ok(to_binary(&LockTiersResponse { tiers })?)
+}
+
+fn share_rate(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let share_token = SHARE_TOKEN.may_load(deps.storage)?;
+    let bonded : Uint128 = SHARE_POOL
+        .may_load(deps.storage)?
+        .map(|pool| pool.principal)
+        .unwrap_or_default();
+    let supply : Uint128 = match &share_token {
+        Some(address) => share_supply(&ShareTokenContract { address: address.clone() })?,
+        None => Uint128::zero(),
+    };
+    let rate = if supply.is_zero() {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(bonded, supply)
+    };
+
+    Ok(to_binary(&ShareRateResponse {
+        share_token,
+        bonded,
+        supply,
+        rate,
+    })?)
+}
+
+fn compound_history(
+    deps: Deps, _env: Env,
+    token_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let records = COMPOUND_HISTORY
+        .prefix(&token_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| Ok(item?.1))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&CompoundHistoryResponse { records })?)
+}
+
+fn validator_totals(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let delegations = VALIDATOR_TOTALS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (validator, amount) = item?;
+            Ok(PositionDelegation { validator, amount })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(to_binary(&ValidatorTotalsResponse { delegations })?)
+}
+
+fn validator_strategy(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let strategy = VALIDATOR_STRATEGY.load(deps.storage)?;
+    Ok(to_binary(&ValidatorStrategyResponse { strategy })?)
+}
+
+fn claims(
+    deps: Deps, _env: Env,
+    address: String,
+) -> Result<Binary, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let claims = CLAIMS.may_load(deps.storage, &address)?.unwrap_or_default();
+    Ok(to_binary(&ClaimsResponse { claims })?)
+}
+
+fn next_token_id(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let token_id = to_token_id(next_position_seq(deps.storage)?);
+    Ok(to_binary(&NextTokenIdResponse { token_id })?)
+}
+
+fn position(
+    deps: Deps, env: Env,
+    token_id: String,
+) -> Result<Binary, ContractError> {
+    let position = find_position(deps.storage, &token_id)?;
+    let (reward_index, _, _) = current_reward_index(deps, &env)?;
+    let res = to_position_response(deps.storage, &env, token_id, position, reward_index)?;
+    Ok(to_binary(&res)?)
+}
+
+fn positions(
+    deps: Deps, env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Binary, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let (reward_index, _, _) = current_reward_index(deps, &env)?;
+
+    let positions = tokens()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (token_id, position) = item?;
+            to_position_response(deps.storage, &env, token_id, position, reward_index)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(to_binary(&PositionsResponse { positions })?)
+}
+
+fn positions_by_owner(
+    deps: Deps, env: Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Binary, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let (reward_index, _, _) = current_reward_index(deps, &env)?;
+
+    let positions = tokens()
+        .idx
+        .owner
+        .prefix(owner)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (token_id, position) = item?;
+            to_position_response(deps.storage, &env, token_id, position, reward_index)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(to_binary(&PositionsResponse { positions })?)
+}
+
+// the reward is earned on the amount before the slashes the position hasn't
+// seen yet, and with the boost of a lock that expired since it was touched
+fn to_position_response(
+    storage: &dyn Storage,
+    env: &Env,
+    token_id: String,
+    mut position: Position,
+    reward_index: Decimal,
+) -> StdResult<PositionResponse> {
+    scale_by_slashes(storage, &mut position)?;
+    end_expired_lock(&mut position, reward_index, env.block.time);
+    let reward : Uint128 = position.accrued_reward + calc_reward(position_weight(&position, env.block.time), position.reward_index, reward_index);
+    Ok(PositionResponse {
+        token_id,
+        owner: position.owner.clone(),
+        amount: position.principal,
+        reward,
+        denom: position.denom,
+        delegations: position.delegations,
+        start_height: position.start_height,
+        start_time: position.start_time,
+        compounding: position.compounding,
+        lock: position.lock,
+    })
+}
+
+fn minter(
+    deps: Deps, _env: Env,
+) -> Result<Binary, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    Ok(Binary(to_vec(&contract.minter())?))
+}
+
+fn owner_of(deps: Deps, _env: Env,
+    token_id: String,
+    include_expired: bool
+) -> Result<Binary, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    Ok(Binary(to_vec(&contract.owner_of(token_id, include_expired))?))
+}
+
+fn get_own_address_via_callees_get_caller_address(
+    deps: Deps,
+    _env: Env,
+) -> Result<Binary, ContractError> {
+    let address : String = CALLEE_CONTRACT_ADDRESS.load(deps.storage)?;
+    let contract = CalleeContract { address: Addr::unchecked(address.clone()) };
+    Ok(Binary(to_vec(&contract.caller_address())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, coins, CosmosMsg, FullDelegation};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::OwnedDeps;
+
+    use super::*;
+
+    // flat fee of 10 on stake and swap
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        let flat_fee = OperationFee { flat: Uint128::new(10), ..OperationFee::default() };
+        let msg = InstantiateMsg {
+            callee_contract_address: Addr::unchecked("cw721"),
+            fee: FeeConfig {
+                stake: flat_fee.clone(),
+                unstake: OperationFee::default(),
+                swap: flat_fee,
+                performance: OperationFee::default(),
+            },
+            denom: "ucony".to_string(),
+            unbonding_period: None,
+            validator_strategy: None,
+            lock_tiers: None,
+            early_exit_penalty: None,
+            penalty_destination: None,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        deps
+    }
+
+    fn save_position(storage: &mut dyn Storage, token_id: &str, owner: &str) {
+        tokens().save(storage, token_id.to_string(), &Position {
+            owner: Addr::unchecked(owner),
+            principal: Uint128::new(100),
+            denom: "ucony".to_string(),
+            delegations: vec![],
+            start_height: 1,
+            start_time: mock_env().block.time,
+            reward_index: Decimal::zero(),
+            accrued_reward: Uint128::zero(),
+            compounding: false,
+            lock: None,
+            slash_seq: 0,
+        }).unwrap();
+    }
+
+    // bond the position on the given validators, replacing its delegations
+    fn delegate(storage: &mut dyn Storage, token_id: &str, delegations: &[(&str, u128)]) {
+        let mut position : Position = tokens().load(storage, token_id.to_string()).unwrap();
+        let weight : Uint128 = position_weight(&position, mock_env().block.time);
+        sub_validator_totals(storage, &position.delegations).unwrap();
+        position.delegations = delegations
+            .iter()
+            .map(|(validator, amount)| PositionDelegation {
+                validator: validator.to_string(),
+                amount: Uint128::new(*amount),
+            })
+            .collect();
+        position.principal = position.delegations.iter().map(|delegation| delegation.amount).sum();
+        add_validator_totals(storage, &position.delegations).unwrap();
+        update_total_weight(storage, weight, position_weight(&position, mock_env().block.time)).unwrap();
+        tokens().save(storage, token_id.to_string(), &position).unwrap();
+    }
+
+    fn full_delegation(validator: &str, amount: u128) -> FullDelegation {
+        FullDelegation {
+            delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            validator: validator.to_string(),
+            amount: coin(amount, "ucony"),
+            can_redelegate: coin(amount, "ucony"),
+            accumulated_rewards: vec![],
+        }
+    }
+
+    fn token_ids_of(deps: Deps, owner: &str, start_after: Option<&str>) -> Vec<String> {
+        let msg = QueryMsg::PositionsByOwner {
+            owner: owner.to_string(),
+            start_after: start_after.map(String::from),
+            limit: None,
+        };
+        let res : PositionsResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.positions.into_iter().map(|position| position.token_id).collect()
+    }
+
+    #[test]
+    fn token_ids() {
+        let mut deps = setup();
+        let next_token_id = |deps: Deps| -> String {
+            let res : NextTokenIdResponse =
+                from_binary(&query(deps, mock_env(), QueryMsg::NextTokenId {}).unwrap()).unwrap();
+            res.token_id
+        };
+        assert_eq!("cw721_1", next_token_id(deps.as_ref()));
+
+        // stakes in the same block get their own ids
+        assert_eq!("cw721_1", reserve_token_id(deps.as_mut().storage).unwrap());
+        assert_eq!("cw721_2", reserve_token_id(deps.as_mut().storage).unwrap());
+        assert_eq!("cw721_3", next_token_id(deps.as_ref()));
+
+        // ids taken from block heights before the sequence existed are skipped
+        save_position(deps.as_mut().storage, "cw721_3", "seoul");
+        save_position(deps.as_mut().storage, "cw721_4", "seoul");
+        assert_eq!("cw721_5", next_token_id(deps.as_ref()));
+        assert_eq!("cw721_5", reserve_token_id(deps.as_mut().storage).unwrap());
+        assert_eq!(6, POSITION_SEQ.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn positions_by_owner() {
+        let mut deps = mock_dependencies();
+        DENOM.save(deps.as_mut().storage, &"ucony".to_string()).unwrap();
+        REWARD_INDEX.save(deps.as_mut().storage, &Decimal::zero()).unwrap();
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(300)).unwrap();
+        PENDING_PENALTIES.save(deps.as_mut().storage, &vec![]).unwrap();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        save_position(deps.as_mut().storage, "cw721_2", "busan");
+        save_position(deps.as_mut().storage, "cw721_3", "seoul");
+
+        assert_eq!(vec!["cw721_1", "cw721_3"], token_ids_of(deps.as_ref(), "seoul", None));
+        assert_eq!(vec!["cw721_3"], token_ids_of(deps.as_ref(), "seoul", Some("cw721_1")));
+
+        // the index follows the nft to its new owner
+        set_position_owner(deps.as_mut().storage, "cw721_1", Addr::unchecked("busan")).unwrap();
+        assert_eq!(vec!["cw721_3"], token_ids_of(deps.as_ref(), "seoul", None));
+        assert_eq!(vec!["cw721_1", "cw721_2"], token_ids_of(deps.as_ref(), "busan", None));
+
+        set_position_owner(deps.as_mut().storage, "cw721_9", Addr::unchecked("busan")).unwrap_err();
+    }
+
+    #[test]
+    fn nft_transfers() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        save_position(deps.as_mut().storage, "cw721_2", "seoul");
+
+        let err = nft_transferred_by(deps.as_mut(), Addr::unchecked("seoul"), "cw721_1".into(), "busan".into())
+            .unwrap_err();
+        assert_eq!(StdError::generic_err("Unauthorized"), err);
+        assert_eq!(vec!["cw721_1", "cw721_2"], token_ids_of(deps.as_ref(), "seoul", None));
+
+        let attributes = nft_transferred_by(deps.as_mut(), Addr::unchecked("cw721"), "cw721_1".into(), "busan".into())
+            .unwrap();
+        assert!(attributes.contains(&Attribute::new("owner", "busan")));
+        assert_eq!(vec!["cw721_2"], token_ids_of(deps.as_ref(), "seoul", None));
+        assert_eq!(vec!["cw721_1"], token_ids_of(deps.as_ref(), "busan", None));
+        assert_eq!(Addr::unchecked("busan"), find_position(deps.as_ref().storage, "cw721_1").unwrap().owner);
+
+        // unknown positions aren't created by a transfer
+        nft_transferred_by(deps.as_mut(), Addr::unchecked("cw721"), "cw721_9".into(), "busan".into()).unwrap_err();
+        assert_eq!(vec!["cw721_1"], token_ids_of(deps.as_ref(), "busan", None));
+    }
+
+    #[test]
+    fn slashes() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        save_position(deps.as_mut().storage, "cw721_2", "busan");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(200)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+        delegate(deps.as_mut().storage, "cw721_2", &[("valoper1", 50), ("valoper2", 50)]);
+
+        // valoper1 lost half, valoper2 reads a few units short from rounding
+        REWARD_INDEX.save(deps.as_mut().storage, &Decimal::one()).unwrap();
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 75), full_delegation("valoper2", 45)]);
+        reconcile_slashes(deps.as_mut(), &mock_env()).unwrap();
+        let event : SlashEvent = SLASH_EVENTS.load(deps.as_ref().storage, 0).unwrap();
+        assert_eq!(("valoper1", Decimal::percent(50), Uint128::new(75)), (event.validator.as_str(), event.ratio, event.amount));
+        assert_eq!(Decimal::one(), event.reward_index);
+        assert_eq!(1, SLASH_EVENT_SEQ.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(75), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper1").unwrap());
+        assert_eq!(Uint128::new(50), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper2").unwrap());
+
+        // the lost amount stops earning before the positions are scaled
+        assert_eq!(Uint128::new(125), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(75), UNAPPLIED_SLASH_WEIGHT.load(deps.as_ref().storage).unwrap());
+
+        // the same balances don't count as another slash
+        reconcile_slashes(deps.as_mut(), &mock_env()).unwrap();
+        assert_eq!(1, SLASH_EVENT_SEQ.load(deps.as_ref().storage).unwrap());
+
+        // positions are scaled when they are loaded, queries show the scaled
+        // amount, earning on the amount before the slash only up to the slash
+        REWARD_INDEX.save(deps.as_mut().storage, &Decimal::percent(200)).unwrap();
+        let msg = QueryMsg::Position { token_id: "cw721_2".to_string() };
+        let res : PositionResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!((Uint128::new(75), Uint128::new(175)), (res.amount, res.reward));
+        assert_eq!(Uint128::new(125), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+
+        let position = load_position(deps.as_mut().storage, &mock_env(), "cw721_1").unwrap();
+        assert_eq!(Uint128::new(50), position.principal);
+        assert_eq!((Uint128::new(100), Decimal::one()), (position.accrued_reward, position.reward_index));
+        assert_eq!(Uint128::new(50), POSITION_SLASHES.load(deps.as_ref().storage, ("cw721_1", 0)).unwrap());
+        let position = load_position(deps.as_mut().storage, &mock_env(), "cw721_2").unwrap();
+        assert_eq!(Uint128::new(75), position.principal);
+        assert_eq!(
+            vec![
+                PositionDelegation { validator: "valoper1".to_string(), amount: Uint128::new(25) },
+                PositionDelegation { validator: "valoper2".to_string(), amount: Uint128::new(50) },
+            ],
+            position.delegations
+        );
+        assert_eq!(Uint128::new(25), POSITION_SLASHES.load(deps.as_ref().storage, ("cw721_2", 0)).unwrap());
+        assert_eq!(Uint128::new(125), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::zero(), UNAPPLIED_SLASH_WEIGHT.load(deps.as_ref().storage).unwrap());
+
+        // a position sees each slash once
+        load_position(deps.as_mut().storage, &mock_env(), "cw721_1").unwrap();
+        assert_eq!(Uint128::new(125), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn owner_needs_no_callee() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+
+        // the stored owner is enough, only others are checked with the callee
+        let msg = ExecuteMsg::SetCompounding { token_id: "cw721_1".to_string(), enabled: true };
+        execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), msg).unwrap();
+        assert!(tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap().compounding);
+    }
+
+    #[test]
+    fn lock_expiry() {
+        let mut deps = setup();
+        let mut env = mock_env();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 100)]);
+
+        // boosted twice until the lock expires
+        let mut position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        position.lock = Some(PositionLock {
+            duration: 100,
+            multiplier: Decimal::percent(200),
+            expires_at: env.block.time.plus_seconds(100),
+        });
+        tokens().save(deps.as_mut().storage, "cw721_1".to_string(), &position).unwrap();
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(200)).unwrap();
+        assert_eq!(Uint128::new(200), position_weight(&position, env.block.time.plus_seconds(99)));
+        assert_eq!(Uint128::new(100), position_weight(&position, env.block.time.plus_seconds(100)));
+
+        // the reward up to the expiry is earned on the boosted weight
+        REWARD_INDEX.save(deps.as_mut().storage, &Decimal::one()).unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+        let msg = QueryMsg::Position { token_id: "cw721_1".to_string() };
+        let res : PositionResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!((Uint128::new(200), None), (res.reward, res.lock));
+
+        // rewards withdrawn after the expiry are shared without the boost,
+        // even though the position isn't touched
+        let rewarded = FullDelegation { accumulated_rewards: coins(100, "ucony"), ..full_delegation("valoper1", 100) };
+        deps.querier.update_staking("ucony", &[], &[rewarded]);
+        update_reward_index(deps.as_mut(), &env).unwrap();
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 100)]);
+        let position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        assert_eq!((None, Uint128::new(200)), (position.lock, position.accrued_reward));
+        assert_eq!(Uint128::new(100), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Decimal::percent(200), REWARD_INDEX.load(deps.as_ref().storage).unwrap());
+        let msg = QueryMsg::Position { token_id: "cw721_1".to_string() };
+        let res : PositionResponse = from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(Uint128::new(300), res.reward);
+
+        // the added amount isn't boosted either
+        let msg = ExecuteMsg::StakeMore { token_id: "cw721_1".to_string() };
+        execute(deps.as_mut(), env, mock_info("seoul", &coins(110, "ucony")), msg).unwrap();
+        let position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        assert_eq!(Uint128::new(200), position.principal);
+        assert_eq!(Uint128::new(300), position.accrued_reward);
+        assert_eq!(Uint128::new(200), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn locked_position() {
+        let mut deps = setup();
+        let env = mock_env();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 100)]);
+        let expires_at = env.block.time.plus_seconds(100);
+        let mut position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        position.lock = Some(PositionLock { duration: 100, multiplier: Decimal::one(), expires_at });
+        tokens().save(deps.as_mut().storage, "cw721_1".to_string(), &position).unwrap();
+
+        // neither unbonded nor topped up before the expiry
+        let token_id = "cw721_1".to_string();
+        for (msg, funds) in [
+            (ExecuteMsg::Unbond { token_id: token_id.clone() }, vec![]),
+            (ExecuteMsg::Reward { token_id: token_id.clone() }, vec![]),
+            (ExecuteMsg::StakeMore { token_id }, coins(110, "ucony")),
+        ] {
+            let err = execute(deps.as_mut(), env.clone(), mock_info("seoul", &funds), msg).unwrap_err();
+            assert!(matches!(err, ContractError::PositionLocked { expires_at: at } if at == expires_at));
+        }
+        assert_eq!(position, tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap());
+    }
+
+    #[test]
+    fn lock_tiers() {
+        let mut deps = setup();
+        let env = mock_env();
+        let tier = |duration: u64, multiplier: u64| LockTier { duration, multiplier: Decimal::percent(multiplier) };
+
+        assert!(validate_lock_tiers(&[tier(100, 100), tier(200, 150)]).is_ok());
+        let err = validate_lock_tiers(&[tier(100, 99)]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidLockTiers(_)));
+        let err = validate_lock_tiers(&[tier(100, 100), tier(100, 150)]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidLockTiers(_)));
+
+        // 5% over the year since the checkpoint, boosted by each tier
+        LOCK_TIERS.save(deps.as_mut().storage, &vec![tier(100, 100), tier(200, 200)]).unwrap();
+        REWARD_CHECKPOINTS.save(deps.as_mut().storage, &vec![RewardCheckpoint {
+            time: env.block.time.minus_seconds(SECONDS_PER_YEAR),
+            index: Decimal::zero(),
+        }]).unwrap();
+        REWARD_INDEX.save(deps.as_mut().storage, &Decimal::percent(5)).unwrap();
+        let res : LockTiersResponse = from_binary(&query(deps.as_ref(), env, QueryMsg::LockTiers {}).unwrap()).unwrap();
+        let aprs : Vec<(u64, Decimal)> = res.tiers.into_iter().map(|tier| (tier.duration, tier.apr)).collect();
+        assert_eq!(vec![(100, Decimal::percent(5)), (200, Decimal::percent(10))], aprs);
+    }
+
+    #[test]
+    fn compound() {
+        let mut deps = setup();
+        let mut fee : FeeConfig = FEE.load(deps.as_ref().storage).unwrap();
+        fee.performance.flat = Uint128::new(5);
+        FEE.save(deps.as_mut().storage, &fee).unwrap();
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(300)).unwrap();
+        for (token_id, compounding) in [("cw721_1", true), ("cw721_2", false), ("cw721_3", true)] {
+            save_position(deps.as_mut().storage, token_id, "seoul");
+            delegate(deps.as_mut().storage, token_id, &[("valoper1", 100)]);
+            tokens().update(deps.as_mut().storage, token_id.to_string(), |position| -> StdResult<_> {
+                Ok(Position { compounding, ..position.unwrap() })
+            }).unwrap();
+        }
+
+        // 150 of rewards, 50 for each position
+        let delegation = FullDelegation {
+            accumulated_rewards: coins(150, "ucony"),
+            ..full_delegation("valoper1", 300)
+        };
+        deps.querier.update_staking("ucony", &[], &[delegation]);
+
+        // one page at a time, positions that don't compound are skipped
+        let msg = ExecuteMsg::Compound { start_after: None, limit: Some(1) };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap();
+        assert_eq!(
+            vec![("compounded", "45"), ("fee", "5"), ("last_token_id", "cw721_1")],
+            res.attributes[2..].iter().map(|attr| (attr.key.as_str(), attr.value.as_str())).collect::<Vec<_>>()
+        );
+        let position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        assert_eq!(Uint128::new(145), position.principal);
+        assert_eq!(Uint128::zero(), position.accrued_reward);
+        assert_eq!(
+            vec![PositionDelegation { validator: "valoper1".to_string(), amount: Uint128::new(145) }],
+            position.delegations
+        );
+        let record : CompoundRecord = COMPOUND_HISTORY.load(deps.as_ref().storage, ("cw721_1", 12_345)).unwrap();
+        assert_eq!(
+            (Uint128::new(50), Uint128::new(5), Uint128::new(45)),
+            (record.reward, record.fee, record.compounded)
+        );
+        assert_eq!(Uint128::new(5), TREASURY.load(deps.as_ref().storage, "ucony").unwrap());
+        assert_eq!(Uint128::new(345), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+
+        // the rewards were withdrawn on the first page
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 345)]);
+        let msg = ExecuteMsg::Compound { start_after: Some("cw721_1".to_string()), limit: None };
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap();
+        let principals : Vec<Uint128> = ["cw721_2", "cw721_3"]
+            .iter()
+            .map(|token_id| tokens().load(deps.as_ref().storage, token_id.to_string()).unwrap().principal)
+            .collect();
+        assert_eq!(vec![Uint128::new(100), Uint128::new(145)], principals);
+    }
+
+    #[test]
+    fn compound_after_full_slash() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[]);
+        add_liability(deps.as_mut().storage, "ucony", Uint128::new(50)).unwrap();
+
+        // the reward stays accrued and owed instead of becoming unbacked principal
+        let mut position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        position.accrued_reward = Uint128::new(50);
+        let performance_fee : OperationFee = FEE.load(deps.as_ref().storage).unwrap().performance;
+        let compounded = compound_position(
+            deps.as_mut().storage,
+            &mock_env(),
+            "cw721_1",
+            &mut position,
+            Decimal::zero(),
+            &performance_fee,
+        ).unwrap();
+        assert_eq!(None, compounded);
+        assert_eq!((Uint128::zero(), Uint128::new(50)), (position.principal, position.accrued_reward));
+        assert_eq!(Uint128::new(50), LIABILITIES.load(deps.as_ref().storage, "ucony").unwrap());
+        assert!(TREASURY.may_load(deps.as_ref().storage, "ucony").unwrap().is_none());
+    }
+
+    #[test]
+    fn share_pool() {
+        let mut deps = setup();
+        let env = mock_env();
+
+        // the first stake mints 1:1
+        assert_eq!(Uint128::new(100), shares_for_amount(Uint128::new(100), Uint128::zero(), Uint128::zero()).unwrap());
+
+        // compounding raises the pool, so later stakes mint fewer shares
+        SHARE_POOL.save(deps.as_mut().storage, &Position {
+            owner: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            principal: Uint128::new(100),
+            denom: "ucony".to_string(),
+            delegations: vec![PositionDelegation { validator: "valoper1".to_string(), amount: Uint128::new(100) }],
+            start_height: 1,
+            start_time: env.block.time,
+            reward_index: Decimal::zero(),
+            accrued_reward: Uint128::zero(),
+            compounding: true,
+            lock: None,
+            slash_seq: 0,
+        }).unwrap();
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        add_liability(deps.as_mut().storage, "ucony", Uint128::new(50)).unwrap();
+        let (pool, msgs) = compound_share_pool(deps.as_mut().storage, &env, Decimal::percent(50)).unwrap();
+        assert_eq!(Uint128::new(150), pool.principal);
+        assert_eq!(1, msgs.len());
+        assert_eq!(Uint128::new(60), shares_for_amount(Uint128::new(90), Uint128::new(100), pool.principal).unwrap());
+
+        // shares redeem their part of the pool
+        assert_eq!(Uint128::new(75), amount_for_shares(Uint128::new(50), Uint128::new(100), pool.principal).unwrap());
+        assert_eq!(pool.principal, amount_for_shares(Uint128::new(100), Uint128::new(100), pool.principal).unwrap());
+        let err = amount_for_shares(Uint128::new(101), Uint128::new(100), pool.principal).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidShareAmount { supply } if supply == Uint128::new(100)));
+
+        // dust worth less than a share, or less than a unit, is rejected
+        let err = shares_for_amount(Uint128::new(1), Uint128::new(100), pool.principal).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidShareAmount { supply } if supply == Uint128::new(100)));
+        let err = amount_for_shares(Uint128::new(1), Uint128::new(1000), Uint128::new(1)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidShareAmount { supply } if supply == Uint128::new(1000)));
+        let err = amount_for_shares(Uint128::zero(), Uint128::new(100), pool.principal).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidShareAmount { .. }));
+
+        // shares are liquid, so they can't be locked
+        let msg = ExecuteMsg::Stake { validator: None, liquid: Some(true), lock_duration: Some(100) };
+        let err = execute(deps.as_mut(), env, mock_info("seoul", &coins(100, "ucony")), msg).unwrap_err();
+        assert!(matches!(err, ContractError::LiquidStakeLocked {}));
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = setup();
+
+        // only the owner or the guardian can pause
+        let set_paused = ExecuteMsg::SetPaused { stake: Some(true), unstake: None, swap: Some(true) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), set_paused.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let update_guardian = ExecuteMsg::UpdateGuardian { guardian: Some("guardian".to_string()) };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), update_guardian).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), set_paused).unwrap();
+
+        let stake = ExecuteMsg::Stake { validator: None, liquid: None, lock_duration: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ucony")), stake).unwrap_err();
+        assert!(matches!(err, ContractError::Paused { operation } if operation == "stake"));
+        let swap = ExecuteMsg::Swap { denom_to: "ubrown".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ucony")), swap).unwrap_err();
+        assert!(matches!(err, ContractError::Paused { operation } if operation == "swap"));
+
+        // matured claims are still paid out
+        push_claim(deps.as_mut().storage, &Addr::unchecked("seoul"), Claim {
+            amount: Uint128::new(100),
+            denom: "ucony".to_string(),
+            release_at: mock_env().block.time,
+        }).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::Claim {}).unwrap();
+        assert_eq!(1, res.messages.len());
+
+        let status : StatusResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Status {}).unwrap()).unwrap();
+        assert_eq!(PauseFlags { stake: true, unstake: false, swap: true }, status.paused);
+        assert_eq!(Some(Addr::unchecked("guardian")), status.guardian);
+    }
+
+    #[test]
+    fn stake_errors() {
+        let mut deps = setup();
+        let stake = ExecuteMsg::Stake { validator: None, liquid: None, lock_duration: None };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ubrown")), stake.clone())
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenom {}));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(10, "ucony")), stake.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientFunds { fund, fee } if fund == Uint128::new(10) && fee == Uint128::new(10)
+        ));
+
+        // the mock chain has no validators
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ucony")), stake)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::NoValidators {}));
+    }
+
+    #[test]
+    fn unstake_errors() {
+        let mut deps = setup();
+
+        let unbond = ExecuteMsg::Unbond { token_id: "cw721_1".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), unbond).unwrap_err();
+        assert!(matches!(err, ContractError::PositionNotFound { token_id } if token_id == "cw721_1"));
+
+        // matured claims of one denom are summed
+        let claim = Claim {
+            amount: Uint128::MAX,
+            denom: "ucony".to_string(),
+            release_at: mock_env().block.time,
+        };
+        CLAIMS.save(deps.as_mut().storage, &Addr::unchecked("seoul"), &vec![claim.clone(), claim]).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::Claim {}).unwrap_err();
+        assert!(matches!(err, ContractError::Overflow(_)));
+    }
+
+    #[test]
+    fn swap_reserve() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 100)]);
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(100, "ucony"));
+
+        // 90 after the fee fits the balance, but 50 of it is owed to the unbonding staker
+        let msg = ExecuteMsg::PartialUnbond { token_id: "cw721_1".to_string(), amount: Uint128::new(50) };
+        execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), msg).unwrap();
+        assert_eq!(Uint128::new(50), LIABILITIES.load(deps.as_ref().storage, "ucony").unwrap());
+        let swap = ExecuteMsg::Swap { denom_to: "ucony".to_string() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("busan", &coins(100, "ubrown")), swap).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientReserve { denom, available } if denom == "ucony" && available == Uint128::new(50)
+        ));
+
+        // paying the claim out releases it
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(DEFAULT_UNBONDING_PERIOD);
+        execute(deps.as_mut(), env, mock_info("seoul", &[]), ExecuteMsg::Claim {}).unwrap();
+        assert!(LIABILITIES.may_load(deps.as_ref().storage, "ucony").unwrap().is_none());
+    }
+
+    #[test]
+    fn swap_errors() {
+        let mut deps = setup();
+        let swap = ExecuteMsg::Swap { denom_to: "ubrown".to_string() };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), swap.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenom {}));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ubrown")), swap.clone())
+            .unwrap_err();
+        assert!(matches!(err, ContractError::SameDenomSwap {}));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(10, "ucony")), swap.clone())
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds { .. }));
+
+        // 90 after the fee, but only 50 in reserve
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(50, "ubrown"));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ucony")), swap.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InsufficientReserve { denom, available } if denom == "ubrown" && available == Uint128::new(50)
+        ));
+
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(100, "ubrown"));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(100, "ucony")), swap).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn split() {
+        let mut deps = setup();
+        let seoul = Addr::unchecked("seoul");
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 60), ("valoper2", 40)]);
+
+        let amounts = vec![Uint128::new(30), Uint128::new(20)];
+        let (owner, new_token_ids) =
+            split_position(deps.as_mut(), &mock_env(), &seoul, "cw721_1", amounts, Decimal::zero()).unwrap();
+        assert_eq!(seoul, owner);
+        assert_eq!(vec!["cw721_2", "cw721_3"], new_token_ids);
+
+        // the portions add up to the original and nothing is redelegated
+        let principals : Vec<Uint128> = ["cw721_1", "cw721_2", "cw721_3"]
+            .iter()
+            .map(|token_id| tokens().load(deps.as_ref().storage, token_id.to_string()).unwrap().principal)
+            .collect();
+        assert_eq!(vec![Uint128::new(50), Uint128::new(30), Uint128::new(20)], principals);
+        assert_eq!(
+            vec![
+                PositionDelegation { validator: "valoper1".to_string(), amount: Uint128::new(18) },
+                PositionDelegation { validator: "valoper2".to_string(), amount: Uint128::new(12) },
+            ],
+            tokens().load(deps.as_ref().storage, "cw721_2".to_string()).unwrap().delegations
+        );
+        assert_eq!(Uint128::new(100), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(60), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper1").unwrap());
+        assert_eq!(vec!["cw721_1", "cw721_2", "cw721_3"], token_ids_of(deps.as_ref(), "seoul", None));
+
+        // the original has to keep a part
+        let err = split_position(deps.as_mut(), &mock_env(), &seoul, "cw721_1", vec![Uint128::new(50)], Decimal::zero())
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAmount { available } if available == Uint128::new(50)));
+    }
+
+    #[test]
+    fn partial_unbond() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 60), ("valoper2", 40)]);
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 60), full_delegation("valoper2", 40)]);
+
+        // the nft is kept, so the whole amount can't be unbonded
+        for amount in [0, 100] {
+            let msg = ExecuteMsg::PartialUnbond { token_id: "cw721_1".to_string(), amount: Uint128::new(amount) };
+            let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidAmount { available } if available == Uint128::new(100)));
+        }
+
+        let msg = ExecuteMsg::PartialUnbond { token_id: "cw721_1".to_string(), amount: Uint128::new(50) };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), msg).unwrap();
+        assert_eq!(
+            vec![
+                CosmosMsg::Staking(StakingMsg::Undelegate { validator: "valoper1".to_string(), amount: coin(30, "ucony") }),
+                CosmosMsg::Staking(StakingMsg::Undelegate { validator: "valoper2".to_string(), amount: coin(20, "ucony") }),
+            ],
+            res.messages.into_iter().map(|msg| msg.msg).collect::<Vec<_>>()
+        );
+
+        let position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        assert_eq!(Uint128::new(50), position.principal);
+        assert_eq!(1, position.start_height);
+        assert_eq!(Uint128::new(50), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(30), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper1").unwrap());
+        assert_eq!(Uint128::new(20), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper2").unwrap());
+
+        let msg = QueryMsg::Claims { address: "seoul".to_string() };
+        let res : ClaimsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![Claim {
+                amount: Uint128::new(50),
+                denom: "ucony".to_string(),
+                release_at: mock_env().block.time.plus_seconds(DEFAULT_UNBONDING_PERIOD),
+            }],
+            res.claims
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let mut deps = setup();
+        let seoul = Addr::unchecked("seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(300)).unwrap();
+        for (token_id, delegations) in [
+            ("cw721_1", [("valoper1", 30), ("valoper2", 20)]),
+            ("cw721_2", [("valoper1", 18), ("valoper2", 12)]),
+            ("cw721_3", [("valoper1", 12), ("valoper2", 8)]),
+        ] {
+            save_position(deps.as_mut().storage, token_id, "seoul");
+            delegate(deps.as_mut().storage, token_id, &delegations);
+        }
+        let token_ids = vec!["cw721_2".to_string(), "cw721_3".to_string()];
+
+        // principal and weight are kept, delegations combine per validator
+        let position =
+            merge_positions(deps.as_mut(), &mock_env(), &seoul, &token_ids, "cw721_1", Decimal::zero()).unwrap();
+        assert_eq!(Uint128::new(100), position.principal);
+        assert_eq!(
+            vec![
+                PositionDelegation { validator: "valoper1".to_string(), amount: Uint128::new(60) },
+                PositionDelegation { validator: "valoper2".to_string(), amount: Uint128::new(40) },
+            ],
+            position.delegations
+        );
+        assert_eq!(position, tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap());
+        assert_eq!(Uint128::new(100), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+        assert_eq!(vec!["cw721_1"], token_ids_of(deps.as_ref(), "seoul", None));
+
+        // locked positions only merge with the same tier
+        save_position(deps.as_mut().storage, "cw721_4", "seoul");
+        tokens().update(deps.as_mut().storage, "cw721_4".to_string(), |position| -> StdResult<_> {
+            let lock = PositionLock {
+                duration: 100,
+                multiplier: Decimal::percent(200),
+                expires_at: mock_env().block.time.plus_seconds(100),
+            };
+            Ok(Position { lock: Some(lock), ..position.unwrap() })
+        }).unwrap();
+        let err = merge_positions(deps.as_mut(), &mock_env(), &seoul, &["cw721_4".to_string()], "cw721_1", Decimal::zero())
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerge(_)));
+    }
+
+    #[test]
+    fn stake_more_after_full_slash() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[]);
+        let valoper2 = Validator {
+            address: "valoper2".to_string(),
+            commission: Decimal::percent(5),
+            max_commission: Decimal::percent(10),
+            max_change_rate: Decimal::percent(1),
+        };
+        deps.querier.update_staking("ucony", &[valoper2], &[]);
+
+        // nothing is left to split the funds like, so they go through the strategy
+        let msg = ExecuteMsg::StakeMore { token_id: "cw721_1".to_string() };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seoul", &coins(110, "ucony")), msg).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Staking(StakingMsg::Delegate { validator: "valoper2".to_string(), amount: coin(100, "ucony") })],
+            res.messages.into_iter().map(|msg| msg.msg).collect::<Vec<_>>()
+        );
+        let position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        assert_eq!(Uint128::new(100), position.principal);
+        assert_eq!(
+            vec![PositionDelegation { validator: "valoper2".to_string(), amount: Uint128::new(100) }],
+            position.delegations
+        );
+        assert_eq!(Uint128::new(100), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper2").unwrap());
+        assert_eq!(Uint128::new(100), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn claim() {
+        let mut deps = setup();
+        let mut fee : FeeConfig = FEE.load(deps.as_ref().storage).unwrap();
+        fee.unstake.flat = Uint128::new(5);
+        FEE.save(deps.as_mut().storage, &fee).unwrap();
+
+        let unbonding = Claim {
+            amount: Uint128::new(60),
+            denom: "ucony".to_string(),
+            release_at: mock_env().block.time.plus_seconds(1),
+        };
+        for amount in [30, 10] {
+            push_claim(deps.as_mut().storage, &Addr::unchecked("seoul"), Claim {
+                amount: Uint128::new(amount),
+                denom: "ucony".to_string(),
+                release_at: mock_env().block.time,
+            }).unwrap();
+        }
+        push_claim(deps.as_mut().storage, &Addr::unchecked("seoul"), unbonding.clone()).unwrap();
+
+        // matured claims are paid out together, minus the unstake fee
+        let res = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::Claim {}).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send { to_address: "seoul".to_string(), amount: coins(35, "ucony") })],
+            res.messages.into_iter().map(|msg| msg.msg).collect::<Vec<_>>()
+        );
+        assert_eq!(Uint128::new(5), TREASURY.load(deps.as_ref().storage, "ucony").unwrap());
+        assert_eq!(Uint128::new(60), LIABILITIES.load(deps.as_ref().storage, "ucony").unwrap());
+        assert_eq!(vec![unbonding], CLAIMS.load(deps.as_ref().storage, &Addr::unchecked("seoul")).unwrap());
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::Claim {}).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("busan", &[]), ExecuteMsg::Claim {}).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+    }
+
+    #[test]
+    fn rebalance() {
+        let mut deps = setup();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+
+        // valoper1 left the active set
+        let valoper2 = Validator {
+            address: "valoper2".to_string(),
+            commission: Decimal::percent(5),
+            max_commission: Decimal::percent(10),
+            max_change_rate: Decimal::percent(1),
+        };
+        deps.querier.update_staking("ucony", &[valoper2], &[full_delegation("valoper1", 100)]);
+
+        let msg = ExecuteMsg::Rebalance { token_ids: vec!["cw721_1".to_string()] };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Staking(StakingMsg::Redelegate {
+                src_validator: "valoper1".to_string(),
+                dst_validator: "valoper2".to_string(),
+                amount: coin(100, "ucony"),
+            })],
+            res.messages.into_iter().map(|msg| msg.msg).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![PositionDelegation { validator: "valoper2".to_string(), amount: Uint128::new(100) }],
+            tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap().delegations
+        );
+        assert!(VALIDATOR_TOTALS.may_load(deps.as_ref().storage, "valoper1").unwrap().is_none());
+        assert_eq!(Uint128::new(100), VALIDATOR_TOTALS.load(deps.as_ref().storage, "valoper2").unwrap());
+        assert_eq!(Uint128::new(100), TOTAL_WEIGHT.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn early_exit_penalty() {
+        let mut deps = setup();
+        let env = mock_env();
+        save_position(deps.as_mut().storage, "cw721_1", "seoul");
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        delegate(deps.as_mut().storage, "cw721_1", &[("valoper1", 100)]);
+        deps.querier.update_staking("ucony", &[], &[full_delegation("valoper1", 100)]);
+
+        // 10% at the start of the lock, half of it halfway through
+        EARLY_EXIT_PENALTY.save(deps.as_mut().storage, &Decimal::percent(10)).unwrap();
+        let mut position : Position = tokens().load(deps.as_ref().storage, "cw721_1".to_string()).unwrap();
+        position.lock = Some(PositionLock {
+            duration: 100,
+            multiplier: Decimal::one(),
+            expires_at: env.block.time.plus_seconds(50),
+        });
+        assert_eq!(Uint128::new(5), calc_early_exit_penalty(deps.as_ref(), &env, &position).unwrap());
+        position.lock = None;
+        assert_eq!(Uint128::zero(), calc_early_exit_penalty(deps.as_ref(), &env, &position).unwrap());
+
+        // unbonded penalties go to their destination, the rest stays pending
+        let penalty = |amount: u128, release_at: Timestamp, destination: PenaltyDestination| PendingPenalty {
+            amount: Uint128::new(amount),
+            denom: "ucony".to_string(),
+            release_at,
+            destination,
+        };
+        let unbonding = penalty(30, env.block.time.plus_seconds(1), PenaltyDestination::Treasury {});
+        PENDING_PENALTIES.save(deps.as_mut().storage, &vec![
+            penalty(50, env.block.time, PenaltyDestination::Stakers {}),
+            penalty(20, env.block.time, PenaltyDestination::Treasury {}),
+            unbonding.clone(),
+        ]).unwrap();
+        add_liability(deps.as_mut().storage, "ucony", Uint128::new(100)).unwrap();
+
+        update_reward_index(deps.as_mut(), &env).unwrap();
+        assert_eq!(Decimal::percent(50), REWARD_INDEX.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(20), TREASURY.load(deps.as_ref().storage, "ucony").unwrap());
+        assert_eq!(vec![unbonding], PENDING_PENALTIES.load(deps.as_ref().storage).unwrap());
+        assert_eq!(Uint128::new(80), LIABILITIES.load(deps.as_ref().storage, "ucony").unwrap());
+    }
+
+    #[test]
+    fn treasury() {
+        let mut deps = setup();
+        collect_fee(deps.as_mut().storage, "ucony", Uint128::new(50)).unwrap();
+
+        let msg = ExecuteMsg::WithdrawFees {
+            denom: "ucony".to_string(),
+            amount: Uint128::new(30),
+            recipient: "treasurer".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send { to_address: "treasurer".to_string(), amount: coins(30, "ucony") })],
+            res.messages.into_iter().map(|msg| msg.msg).collect::<Vec<_>>()
+        );
+
+        // withdrawing doesn't change what was collected
+        let res : TreasuryBalanceResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TreasuryBalance {}).unwrap()).unwrap();
+        assert_eq!(coins(20, "ucony"), res.balance);
+        let res : FeesCollectedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FeesCollected {}).unwrap()).unwrap();
+        assert_eq!(coins(50, "ucony"), res.fees);
+
+        let msg = ExecuteMsg::WithdrawFees {
+            denom: "ucony".to_string(),
+            amount: Uint128::new(20),
+            recipient: "treasurer".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+        let res : TreasuryBalanceResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TreasuryBalance {}).unwrap()).unwrap();
+        assert!(res.balance.is_empty());
+    }
+
+    #[test]
+    fn ownership() {
+        let mut deps = setup();
+        let propose = |new_owner: &str| ExecuteMsg::ProposeNewOwner { new_owner: new_owner.to_string() };
+        let ownership = |deps: Deps| -> OwnershipResponse {
+            from_binary(&query(deps, mock_env(), QueryMsg::Ownership {}).unwrap()).unwrap()
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), propose("seoul")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose("busan")).unwrap();
+
+        // only the pending owner can accept
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::AcceptOwnership {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        assert_eq!(
+            OwnershipResponse { owner: Some(Addr::unchecked("admin")), pending_owner: Some(Addr::unchecked("busan")) },
+            ownership(deps.as_ref())
+        );
+
+        execute(deps.as_mut(), mock_env(), mock_info("busan", &[]), ExecuteMsg::AcceptOwnership {}).unwrap();
+        assert_eq!(OwnershipResponse { owner: Some(Addr::unchecked("busan")), pending_owner: None }, ownership(deps.as_ref()));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), propose("admin")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // renouncing drops the pending owner as well
+        execute(deps.as_mut(), mock_env(), mock_info("busan", &[]), propose("seoul")).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("busan", &[]), ExecuteMsg::RenounceOwnership {}).unwrap();
+        assert_eq!(OwnershipResponse { owner: None, pending_owner: None }, ownership(deps.as_ref()));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), ExecuteMsg::AcceptOwnership {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(deps.as_mut(), mock_env(), mock_info("busan", &[]), propose("busan")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn withdraw_fees_errors() {
+        let mut deps = setup();
+        collect_fee(deps.as_mut().storage, "ucony", Uint128::new(50)).unwrap();
+        let withdraw = |denom: &str, amount: u128| ExecuteMsg::WithdrawFees {
+            denom: denom.to_string(),
+            amount: Uint128::new(amount),
+            recipient: "treasurer".to_string(),
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("seoul", &[]), withdraw("ucony", 50)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        for (denom, amount, balance) in [("ucony", 0, 50), ("ucony", 51, 50), ("ubrown", 1, 0)] {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), withdraw(denom, amount))
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                ContractError::InsufficientTreasury { denom: d, available } if d == denom && available == Uint128::new(balance)
+            ));
+        }
+    }
+
+    #[test]
+    fn update_config_guards() {
+        let mut deps = setup();
+        let update = |unbonding_period, callee_contract_address, share_token| ExecuteMsg::UpdateConfig {
+            fee: None,
+            denom: None,
+            callee_contract_address,
+            unbonding_period,
+            share_token,
+        };
+
+        for period in [0, MAX_UNBONDING_PERIOD + 1] {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), update(Some(period), None, None))
+                .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidConfig(_)));
+        }
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), update(Some(MAX_UNBONDING_PERIOD), None, None))
+            .unwrap();
+        assert_eq!(MAX_UNBONDING_PERIOD, UNBONDING_PERIOD.load(deps.as_ref().storage).unwrap());
+
+        let msg = InstantiateMsg {
+            callee_contract_address: Addr::unchecked("cw721"),
+            fee: FEE.load(deps.as_ref().storage).unwrap(),
+            denom: "ucony".to_string(),
+            unbonding_period: Some(0),
+            validator_strategy: None,
+            lock_tiers: None,
+            early_exit_penalty: None,
+            penalty_destination: None,
+        };
+        let err = instantiate(mock_dependencies().as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidConfig(_)));
+
+        TOTAL_WEIGHT.save(deps.as_mut().storage, &Uint128::new(100)).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), update(None, Some("cw721_v2".into()), None))
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidConfig(_)));
+        assert_eq!("cw721", CALLEE_CONTRACT_ADDRESS.load(deps.as_ref().storage).unwrap());
+
+        SHARE_TOKEN.save(deps.as_mut().storage, &Addr::unchecked("share")).unwrap();
+        SHARE_POOL.save(deps.as_mut().storage, &Position {
+            owner: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            principal: Uint128::new(100),
+            denom: "ucony".to_string(),
+            delegations: vec![],
+            start_height: 1,
+            start_time: mock_env().block.time,
+            reward_index: Decimal::zero(),
+            accrued_reward: Uint128::zero(),
+            compounding: true,
+            lock: None,
+            slash_seq: 0,
+        }).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), update(None, None, Some("share_v2".into())))
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidConfig(_)));
+        assert_eq!(Addr::unchecked("share"), SHARE_TOKEN.load(deps.as_ref().storage).unwrap());
+    }
+
+    #[test]
+    fn migrate_versions() {
+        let mut deps = setup();
+        let msg = MigrateMsg { owner: None };
+
+        migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMigration(_)));
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.x.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Version(_)));
+
+        set_contract_version(deps.as_mut().storage, "cw721-base", CONTRACT_VERSION).unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMigration(_)));
+    }
 }
 
 /*
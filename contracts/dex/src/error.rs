@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{OverflowError, StdError, Timestamp, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,11 +6,89 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
     #[error("Storage: {0}")]
     Storage(String),
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("No matured claims")]
+    NothingToClaim {},
+
+    #[error("Invalid denom received")]
+    InvalidDenom {},
+
+    #[error("Fund {fund} must be greater than the fee {fee}")]
+    InsufficientFunds { fund: Uint128, fee: Uint128 },
+
+    #[error("Cannot swap a denom into itself")]
+    SameDenomSwap {},
+
+    #[error("Reserve of {denom} is only {available}")]
+    InsufficientReserve { denom: String, available: Uint128 },
+
+    #[error("Position {token_id} not found")]
+    PositionNotFound { token_id: String },
+
+    #[error("No active validators to delegate to")]
+    NoValidators {},
+
+    #[error("Validator {validator} is not allowed")]
+    ValidatorNotAllowed { validator: String },
+
+    #[error("A validator must be specified")]
+    ValidatorRequired {},
+
+    #[error("Invalid validator strategy: {0}")]
+    InvalidStrategy(String),
+
+    #[error("Amount must be greater than 0 and less than the position amount {available}")]
+    InvalidAmount { available: Uint128 },
+
+    #[error("Share amount must be greater than 0 and at most the supply {supply}")]
+    InvalidShareAmount { supply: Uint128 },
+
+    #[error("Amount must be greater than 0 and at most the treasury balance {available} of {denom}")]
+    InsufficientTreasury { denom: String, available: Uint128 },
+
+    #[error("Invalid merge: {0}")]
+    InvalidMerge(String),
+
+    #[error("Invalid fee config: {0}")]
+    InvalidFeeConfig(String),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid migration: {0}")]
+    InvalidMigration(String),
+
+    #[error("Invalid version: {0}")]
+    Version(#[from] semver::Error),
+
+    #[error("Early exit penalty must not be greater than 1")]
+    InvalidPenalty {},
+
+    #[error("Liquid staking is not enabled")]
+    LiquidStakingDisabled {},
+
+    #[error("Liquid stakes can't be locked")]
+    LiquidStakeLocked {},
+
+    #[error("No lock tier with duration {duration}")]
+    LockTierNotFound { duration: u64 },
+
+    #[error("Invalid lock tiers: {0}")]
+    InvalidLockTiers(String),
+
+    #[error("Position is locked until {expires_at}")]
+    PositionLocked { expires_at: Timestamp },
+
+    #[error("{operation} is paused")]
+    Paused { operation: String },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -0,0 +1,132 @@
+use cosmwasm_std::Uint128;
+
+use crate::error::ContractError;
+use crate::state::{FeeConfig, OperationFee};
+
+const MAX_BPS: u16 = 10_000;
+
+// reject schedules that could take more than the amount in bps or have
+// bounds that can't be met
+pub fn validate_fee_config(fee: &FeeConfig) -> Result<(), ContractError> {
+    validate_operation_fee("stake", &fee.stake)?;
+    validate_operation_fee("unstake", &fee.unstake)?;
+    validate_operation_fee("swap", &fee.swap)?;
+    validate_operation_fee("performance", &fee.performance)
+}
+
+fn validate_operation_fee(operation: &str, fee: &OperationFee) -> Result<(), ContractError> {
+    if fee.bps > MAX_BPS {
+        return Err(ContractError::InvalidFeeConfig(format!(
+            "{} fee of {} bps is above {}",
+            operation, fee.bps, MAX_BPS,
+        )));
+    }
+    for (i, limit) in fee.limits.iter().enumerate() {
+        if let (Some(min), Some(max)) = (limit.min, limit.max) {
+            if min > max {
+                return Err(ContractError::InvalidFeeConfig(format!(
+                    "{} fee minimum for {} is above its maximum",
+                    operation, limit.denom,
+                )));
+            }
+        }
+        if fee.limits[..i].iter().any(|other| other.denom == limit.denom) {
+            return Err(ContractError::InvalidFeeConfig(format!(
+                "{} fee limits list {} more than once",
+                operation, limit.denom,
+            )));
+        }
+    }
+    Ok(())
+}
+
+// bps of amount plus the flat fee, kept within the limits of denom
+pub fn calc_fee(fee: &OperationFee, denom: &str, amount: Uint128) -> Uint128 {
+    let mut total : Uint128 = amount
+        .multiply_ratio(fee.bps, MAX_BPS)
+        .saturating_add(fee.flat);
+    if let Some(limit) = fee.limits.iter().find(|limit| limit.denom == denom) {
+        if let Some(min) = limit.min {
+            total = total.max(min);
+        }
+        if let Some(max) = limit.max {
+            total = total.min(max);
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::Uint128;
+
+    use super::*;
+    use crate::state::FeeLimit;
+
+    fn operation_fee(bps: u16, flat: u128, limits: Vec<FeeLimit>) -> OperationFee {
+        OperationFee {
+            bps,
+            flat: Uint128::new(flat),
+            limits,
+        }
+    }
+
+    fn limit(denom: &str, min: Option<u128>, max: Option<u128>) -> FeeLimit {
+        FeeLimit {
+            denom: denom.to_string(),
+            min: min.map(Uint128::new),
+            max: max.map(Uint128::new),
+        }
+    }
+
+    fn fee_config(stake: OperationFee) -> FeeConfig {
+        FeeConfig {
+            stake,
+            unstake: OperationFee::default(),
+            swap: OperationFee::default(),
+            performance: OperationFee::default(),
+        }
+    }
+
+    #[test]
+    fn bps_and_flat() {
+        // 1% of 1000 plus 5
+        let fee = operation_fee(100, 5, vec![]);
+        assert_eq!(Uint128::new(15), calc_fee(&fee, "ucony", Uint128::new(1000)));
+
+        // bps round down
+        let fee = operation_fee(30, 0, vec![]);
+        assert_eq!(Uint128::new(2), calc_fee(&fee, "ucony", Uint128::new(999)));
+    }
+
+    #[test]
+    fn limits_per_denom() {
+        let fee = operation_fee(100, 0, vec![
+            limit("ucony", Some(20), Some(50)),
+            limit("ubrown", None, Some(1)),
+        ]);
+        assert_eq!(Uint128::new(20), calc_fee(&fee, "ucony", Uint128::new(1000)));
+        assert_eq!(Uint128::new(30), calc_fee(&fee, "ucony", Uint128::new(3000)));
+        assert_eq!(Uint128::new(50), calc_fee(&fee, "ucony", Uint128::new(100_000)));
+        assert_eq!(Uint128::new(1), calc_fee(&fee, "ubrown", Uint128::new(1000)));
+
+        // denoms without limits only pay bps and flat
+        assert_eq!(Uint128::new(10), calc_fee(&fee, "uother", Uint128::new(1000)));
+    }
+
+    #[test]
+    fn invalid_configs() {
+        validate_fee_config(&fee_config(operation_fee(10_000, 1, vec![]))).unwrap();
+
+        let err = validate_fee_config(&fee_config(operation_fee(10_001, 0, vec![]))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeConfig(_)));
+
+        let limits = vec![limit("ucony", Some(10), Some(5))];
+        let err = validate_fee_config(&fee_config(operation_fee(0, 0, limits))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeConfig(_)));
+
+        let limits = vec![limit("ucony", None, Some(5)), limit("ucony", Some(1), None)];
+        let err = validate_fee_config(&fee_config(operation_fee(0, 0, limits))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFeeConfig(_)));
+    }
+}
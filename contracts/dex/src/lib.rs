@@ -1,4 +1,7 @@
 pub mod contract;
 mod error;
+mod fee;
+mod migrate;
 pub mod msg;
-pub mod state;
\ No newline at end of file
+pub mod state;
+mod strategy;
\ No newline at end of file
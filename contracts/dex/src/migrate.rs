@@ -0,0 +1,178 @@
+use cosmwasm_std::{Addr, Decimal, Env, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::{Item, Map};
+
+use crate::error::ContractError;
+use crate::state::{
+    tokens, FeeConfig, OperationFee, PauseFlags, PenaltyDestination, Position, PositionDelegation,
+    RewardCheckpoint, ValidatorStrategy, DENOM, EARLY_EXIT_PENALTY, FEE, FEES_COLLECTED,
+    LOCK_TIERS, OWNER, PAUSED, PENALTY_DESTINATION, PENDING_PENALTIES, REWARD_CHECKPOINTS,
+    REWARD_INDEX, TOTAL_WEIGHT, TREASURY, UNBONDING_PERIOD, VALIDATOR_STRATEGY, VALIDATOR_TOTALS,
+};
+
+// state of 0.1.0, before positions and fees were typed
+const LEGACY_FEE: Item<String> = Item::new("fee");
+const LEGACY_FEES_COLLECTED: Map<&Addr, Uint128> = Map::new("fees_collected"); // user_addr: fee_collected
+const LEGACY_TOKENS: Map<String, (Uint128, String, String)> = Map::new("tokens"); // token_id: (amount, validator_address, block_height)
+
+// 0.1.0 kept the flat fee as a string and each position as a tuple with a
+// single validator; everything added since starts from its instantiate default.
+// nft_owner looks up the owner of a position nft, 0.1.0 didn't record it
+pub fn migrate_from_v0_1(
+    storage: &mut dyn Storage,
+    env: &Env,
+    owner: Addr,
+    unbonding_period: u64,
+    nft_owner: impl Fn(&str) -> StdResult<Addr>,
+) -> Result<(), ContractError> {
+    OWNER.save(storage, &owner)?;
+
+    // the flat fee was charged on stake and swap
+    let legacy_fee : String = LEGACY_FEE.load(storage)?;
+    let flat : Uint128 = legacy_fee
+        .trim()
+        .parse::<Uint128>()
+        .map_err(|_| ContractError::InvalidMigration(format!("invalid legacy fee {}", legacy_fee)))?;
+    FEE.save(storage, &FeeConfig {
+        stake: OperationFee { flat, ..OperationFee::default() },
+        unstake: OperationFee::default(),
+        swap: OperationFee { flat, ..OperationFee::default() },
+        performance: OperationFee::default(),
+    })?;
+
+    let denom : String = DENOM.load(storage)?;
+    if denom.trim().is_empty() {
+        return Err(ContractError::InvalidMigration("legacy denom is empty".into()));
+    }
+
+    // fees were counted per payer without a denom, but 0.1.0 only charged
+    // them in the staking denom, so their sum moves to the treasury
+    let legacy_fees : Vec<(Addr, Uint128)> = LEGACY_FEES_COLLECTED
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut fees = Uint128::zero();
+    for (payer, fee) in legacy_fees {
+        LEGACY_FEES_COLLECTED.remove(storage, &payer);
+        fees = fees.checked_add(fee)?;
+    }
+    if !fees.is_zero() {
+        TREASURY.save(storage, &denom, &fees)?;
+        FEES_COLLECTED.save(storage, &denom, &fees)?;
+    }
+
+    // positions start at the current reward index, so rewards accrued before
+    // the migration are shared by all positions on the first settlement
+    let legacy_tokens : Vec<(String, (Uint128, String, String))> = LEGACY_TOKENS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut total = Uint128::zero();
+    for (token_id, (amount, validator, height)) in legacy_tokens {
+        let start_height : u64 = height.parse().map_err(|_| {
+            ContractError::InvalidMigration(format!("invalid block height {} of {}", height, token_id))
+        })?;
+
+        // the legacy entry can't be read as a position, so it is dropped
+        // before the indexed save looks for a previous value
+        LEGACY_TOKENS.remove(storage, token_id.clone());
+        tokens().save(storage, token_id.clone(), &Position {
+            owner: nft_owner(&token_id)?,
+            principal: amount,
+            denom: denom.clone(),
+            delegations: vec![PositionDelegation {
+                validator: validator.clone(),
+                amount,
+            }],
+            start_height,
+            // the stake time wasn't recorded, the migration time is the closest known
+            start_time: env.block.time,
+            reward_index: Decimal::zero(),
+            accrued_reward: Uint128::zero(),
+            compounding: false,
+            lock: None,
+            // 0.1 didn't track slashes
+            slash_seq: 0,
+        })?;
+        VALIDATOR_TOTALS.update(storage, &validator, |sum| -> StdResult<_> {
+            Ok(sum.unwrap_or_default().checked_add(amount)?)
+        })?;
+        total = total.checked_add(amount)?;
+    }
+
+    TOTAL_WEIGHT.save(storage, &total)?;
+    REWARD_INDEX.save(storage, &Decimal::zero())?;
+    REWARD_CHECKPOINTS.save(storage, &vec![RewardCheckpoint {
+        time: env.block.time,
+        index: Decimal::zero(),
+    }])?;
+    UNBONDING_PERIOD.save(storage, &unbonding_period)?;
+    VALIDATOR_STRATEGY.save(storage, &ValidatorStrategy::LowestCommission {})?;
+    LOCK_TIERS.save(storage, &vec![])?;
+    EARLY_EXIT_PENALTY.save(storage, &Decimal::zero())?;
+    PENALTY_DESTINATION.save(storage, &PenaltyDestination::Stakers {})?;
+    PENDING_PENALTIES.save(storage, &vec![])?;
+    PAUSED.save(storage, &PauseFlags::default())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{Addr, Uint128};
+
+    use super::*;
+
+    #[test]
+    fn legacy_state() {
+        let mut deps = mock_dependencies();
+        LEGACY_FEE.save(deps.as_mut().storage, &"100".to_string()).unwrap();
+        DENOM.save(deps.as_mut().storage, &"ucony".to_string()).unwrap();
+        LEGACY_FEES_COLLECTED
+            .save(deps.as_mut().storage, &Addr::unchecked("seoul"), &Uint128::new(100))
+            .unwrap();
+        LEGACY_FEES_COLLECTED
+            .save(deps.as_mut().storage, &Addr::unchecked("busan"), &Uint128::new(50))
+            .unwrap();
+        LEGACY_TOKENS
+            .save(deps.as_mut().storage, "cw721_1".into(), &(Uint128::new(900), "val_a".into(), "12".into()))
+            .unwrap();
+        LEGACY_TOKENS
+            .save(deps.as_mut().storage, "cw721_2".into(), &(Uint128::new(100), "val_a".into(), "15".into()))
+            .unwrap();
+
+        let nft_owner = |token_id: &str| Ok(Addr::unchecked(format!("owner_of_{}", token_id)));
+        migrate_from_v0_1(deps.as_mut().storage, &mock_env(), Addr::unchecked("admin"), 60, nft_owner).unwrap();
+
+        let storage = deps.as_ref().storage;
+        assert_eq!(Addr::unchecked("admin"), OWNER.load(storage).unwrap());
+        let fee = FEE.load(storage).unwrap();
+        assert_eq!(Uint128::new(100), fee.stake.flat);
+        assert_eq!(Uint128::new(100), fee.swap.flat);
+        assert_eq!(Uint128::zero(), fee.unstake.flat);
+        assert!(LEGACY_FEES_COLLECTED.keys(storage, None, None, Order::Ascending).next().is_none());
+        assert_eq!(Uint128::new(150), TREASURY.load(storage, "ucony").unwrap());
+        assert_eq!(Uint128::new(150), FEES_COLLECTED.load(storage, "ucony").unwrap());
+
+        let position = tokens().load(storage, "cw721_1".into()).unwrap();
+        assert_eq!(Addr::unchecked("owner_of_cw721_1"), position.owner);
+        assert_eq!(Uint128::new(900), position.principal);
+        assert_eq!("ucony", position.denom);
+        assert_eq!(12, position.start_height);
+        assert_eq!(
+            vec![PositionDelegation { validator: "val_a".into(), amount: Uint128::new(900) }],
+            position.delegations,
+        );
+        assert_eq!(Uint128::new(1000), TOTAL_WEIGHT.load(storage).unwrap());
+        assert_eq!(Uint128::new(1000), VALIDATOR_TOTALS.load(storage, "val_a").unwrap());
+    }
+
+    #[test]
+    fn invalid_legacy_fee() {
+        let mut deps = mock_dependencies();
+        LEGACY_FEE.save(deps.as_mut().storage, &"1ucony".to_string()).unwrap();
+        DENOM.save(deps.as_mut().storage, &"ucony".to_string()).unwrap();
+
+        let nft_owner = |_: &str| Ok(Addr::unchecked("seoul"));
+        let err = migrate_from_v0_1(deps.as_mut().storage, &mock_env(), Addr::unchecked("admin"), 60, nft_owner)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMigration(_)));
+    }
+}
@@ -1,23 +1,106 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Coin, Decimal, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::state::{
+    Claim, CompoundRecord, FeeConfig, LockTier, PauseFlags, PenaltyDestination, PositionDelegation,
+    PositionLock, ValidatorStrategy,
+};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub callee_contract_address: Addr,
-    pub fee: String,
+    pub fee: FeeConfig,
     pub denom: String,
+    // defaults to the cosmos-sdk unbonding period of 21 days
+    pub unbonding_period: Option<u64>,
+    // defaults to LowestCommission
+    pub validator_strategy: Option<ValidatorStrategy>,
+    // defaults to no tiers, positions can't be locked
+    pub lock_tiers: Option<Vec<LockTier>>,
+    // penalty for EmergencyUnbond right after locking, decays to zero at
+    // expiry; defaults to 0
+    pub early_exit_penalty: Option<Decimal>,
+    // defaults to Stakers
+    pub penalty_destination: Option<PenaltyDestination>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {
+    // required when migrating from 0.1.x, which had no owner
+    pub owner: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     CallCallerAddressOf { target: Addr },
     Transfer(TransferMsg),
-    Stake { },
+    // validator is only accepted with the UserSpecified strategy; liquid
+    // mints share tokens instead of a position nft; lock_duration picks a
+    // lock tier, the position can't be unbonded before it expires
+    Stake { validator: Option<String>, liquid: Option<bool>, lock_duration: Option<u64> },
+    // add funds to an existing position, split like its current delegations;
+    // rejected while the position is locked
+    StakeMore { token_id: String },
+    // same as Unbond, kept for existing clients
     Reward { token_id: String },
+    Unbond { token_id: String },
+    // unbond a locked position before expiry, minus the early exit penalty
+    EmergencyUnbond { token_id: String },
+    // unbond part of a position and keep the nft
+    PartialUnbond { token_id: String, amount: Uint128 },
+    // move each amount out of the position into a newly minted position
+    Split { token_id: String, amounts: Vec<Uint128> },
+    // fold the positions in token_ids into the position into and burn their nfts
+    Merge { token_ids: Vec<String>, into: String },
+    SetCompounding { token_id: String, enabled: bool },
+    // re-delegate the rewards of compounding positions a page at a time, the
+    // share pool is compounded with the first page; anyone can call it
+    Compound { start_after: Option<String>, limit: Option<u32> },
+    // burn share tokens for an unbonding claim at the current exchange rate
+    Redeem { amount: Uint128 },
+    // fields left as None keep their value
+    UpdateConfig {
+        fee: Option<FeeConfig>,
+        // only while nothing is bonded
+        denom: Option<String>,
+        // only while nothing is bonded, the new callee must report this
+        // contract as its minter
+        callee_contract_address: Option<String>,
+        // in seconds, at most 60 days
+        unbonding_period: Option<u64>,
+        // only while the share pool is empty, the new token must report this
+        // contract as its minter
+        share_token: Option<String>,
+    },
+    UpdateLockTiers { tiers: Vec<LockTier> },
+    UpdatePenaltyConfig {
+        early_exit_penalty: Option<Decimal>,
+        destination: Option<PenaltyDestination>,
+    },
+    Claim {},
     Swap { denom_to: String },
+    // pay out fees held in the treasury
+    WithdrawFees { denom: String, amount: Uint128, recipient: String },
+    UpdateValidatorStrategy { strategy: ValidatorStrategy },
+    // None removes the keeper
+    UpdateKeeper { keeper: Option<String> },
+    // None removes the guardian
+    UpdateGuardian { guardian: Option<String> },
+    // owner or guardian; flags left as None keep their value
+    SetPaused { stake: Option<bool>, unstake: Option<bool>, swap: Option<bool> },
+    // ownership moves once the new owner accepts, proposing again replaces
+    // the pending owner
+    ProposeNewOwner { new_owner: String },
+    AcceptOwnership {},
+    // leaves the contract without an owner, owner-only messages fail afterwards
+    RenounceOwnership {},
+    // move positions off validators that left the active set, "share_pool"
+    // names the position backing the share token
+    Rebalance { token_ids: Vec<String> },
 }
 
 #[cw_serde]
@@ -45,24 +128,149 @@ pub struct BurnMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetBondItem { idx: String },
-    // GetReward { idx: String },
     GetOwnAddressViaCalleesGetCallerAddress {},
     Minter {},
     OwnerOf { token_id: String, include_expired: bool },
+    Position { token_id: String },
+    Positions { start_after: Option<String>, limit: Option<u32> },
+    // positions whose nft is held by owner
+    PositionsByOwner { owner: String, start_after: Option<String>, limit: Option<u32> },
+    NextTokenId {},
+    Claims { address: String },
+    ValidatorStrategy {},
+    // amount delegated to each validator by all positions together
+    ValidatorTotals {},
+    CompoundHistory { token_id: String, start_after: Option<u64>, limit: Option<u32> },
+    ShareRate {},
+    LockTiers {},
+    // slashes found on the validators, or those applied to one position when
+    // token_id is set; a position records its loss the next time it is touched
+    SlashEvents { token_id: Option<String>, start_after: Option<u64>, limit: Option<u32> },
+    Config {},
+    Ownership {},
+    // all fees ever collected per denom
+    FeesCollected {},
+    // what the treasury holds now per denom
+    TreasuryBalance {},
+    Status {},
 }
 
 #[cw_serde]
-pub struct BonedItemResponse {
+pub struct PositionResponse {
     pub token_id: String,
-    pub status: String,
-    pub staking_denom: String,
-    pub staking_amount: u64,
+    pub owner: Addr,
+    pub amount: Uint128,
+    pub denom: String,
+    pub delegations: Vec<PositionDelegation>,
+    pub start_height: u64,
+    pub start_time: Timestamp,
+    pub compounding: bool,
+    pub lock: Option<PositionLock>,
+    // reward accrued so far, excluding the staked amount
+    pub reward: Uint128,
 }
 
 #[cw_serde]
-pub struct RewardResponse {
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+}
+
+#[cw_serde]
+pub struct NextTokenIdResponse {
     pub token_id: String,
-    pub staking_denom: String,
-    pub staking_amount: u64,
-}
\ No newline at end of file
+}
+
+#[cw_serde]
+pub struct ClaimsResponse {
+    pub claims: Vec<Claim>,
+}
+
+#[cw_serde]
+pub struct ValidatorStrategyResponse {
+    pub strategy: ValidatorStrategy,
+}
+
+#[cw_serde]
+pub struct ValidatorTotalsResponse {
+    pub delegations: Vec<PositionDelegation>,
+}
+
+#[cw_serde]
+pub struct CompoundHistoryResponse {
+    pub records: Vec<CompoundRecord>,
+}
+
+#[cw_serde]
+pub struct ShareRateResponse {
+    pub share_token: Option<Addr>,
+    // amount bonded for share holders
+    pub bonded: Uint128,
+    pub supply: Uint128,
+    // bonded amount per share
+    pub rate: Decimal,
+}
+
+#[cw_serde]
+pub struct LockTiersResponse {
+    pub tiers: Vec<LockTierInfo>,
+}
+
+#[cw_serde]
+pub struct LockTierInfo {
+    pub duration: u64,
+    pub multiplier: Decimal,
+    // estimated from reward index growth over the last 7 to 14 days
+    pub apr: Decimal,
+}
+
+#[cw_serde]
+pub struct SlashEventsResponse {
+    pub events: Vec<SlashEventInfo>,
+}
+
+#[cw_serde]
+pub struct SlashEventInfo {
+    pub id: u64,
+    pub validator: String,
+    pub height: u64,
+    pub time: Timestamp,
+    // share of the delegation left after the slash
+    pub ratio: Decimal,
+    // lost by all positions together
+    pub amount: Uint128,
+    // lost by the queried position
+    pub loss: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct FeesCollectedResponse {
+    pub fees: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct TreasuryBalanceResponse {
+    pub balance: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct OwnershipResponse {
+    // None after the ownership was renounced
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct StatusResponse {
+    pub paused: PauseFlags,
+    pub guardian: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub owner: Option<Addr>,
+    pub callee_contract_address: String,
+    pub share_token: Option<Addr>,
+    pub fee: FeeConfig,
+    pub denom: String,
+    pub unbonding_period: u64,
+}
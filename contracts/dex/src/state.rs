@@ -1,8 +1,226 @@
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
+pub const OWNER: Item<Addr> = Item::new("owner"); // unset once ownership is renounced
+pub const PENDING_OWNER: Item<Addr> = Item::new("pending_owner"); // proposed owner until it accepts
+pub const KEEPER: Item<Addr> = Item::new("keeper"); // may rebalance positions besides the owner
+pub const GUARDIAN: Item<Addr> = Item::new("guardian"); // may pause operations besides the owner
+pub const PAUSED: Item<PauseFlags> = Item::new("paused");
 pub const CALLEE_CONTRACT_ADDRESS: Item<String> = Item::new("callee_contract_address");
-pub const FEE: Item<String> = Item::new("fee");
+pub const SHARE_TOKEN: Item<Addr> = Item::new("share_token"); // cw20 minted for liquid stakes, unset disables them
+pub const FEE: Item<FeeConfig> = Item::new("fee");
 pub const DENOM: Item<String> = Item::new("denom");
-pub const FEES_COLLECTED: Map<&Addr, Uint128> = Map::new("fees_collected"); // user_addr: fee_collected
-pub const TOKENS: Map<String, (Uint128, String, String)> = Map::new("tokens"); // token_id: (amount, validator_address, block_height)
+pub const UNBONDING_PERIOD: Item<u64> = Item::new("unbonding_period"); // seconds until undelegated coins can be claimed
+pub const FEES_COLLECTED: Map<&str, Uint128> = Map::new("fees_collected_denom"); // denom: all fees ever collected
+pub const POSITION_SEQ: Item<u64> = Item::new("position_seq"); // sequence for the next position token_id
+pub const CLAIMS: Map<&Addr, Vec<Claim>> = Map::new("claims"); // user_addr: pending claims
+pub const TOTAL_WEIGHT: Item<Uint128> = Item::new("total_weight"); // sum of all position reward weights
+pub const REWARD_INDEX: Item<Decimal> = Item::new("reward_index"); // delegation rewards accumulated per unit of weight
+pub const REWARD_CHECKPOINTS: Item<Vec<RewardCheckpoint>> = Item::new("reward_checkpoints"); // last two, for the apr estimate
+pub const LOCK_TIERS: Item<Vec<LockTier>> = Item::new("lock_tiers");
+pub const EARLY_EXIT_PENALTY: Item<Decimal> = Item::new("early_exit_penalty"); // share of the amount lost when exiting right after locking
+pub const PENALTY_DESTINATION: Item<PenaltyDestination> = Item::new("penalty_destination");
+pub const PENDING_PENALTIES: Item<Vec<PendingPenalty>> = Item::new("pending_penalties"); // penalties still unbonding
+pub const TREASURY: Map<&str, Uint128> = Map::new("treasury"); // denom: fees and penalties the owner can withdraw
+pub const LIABILITIES: Map<&str, Uint128> = Map::new("liabilities"); // denom: claims, rewards and penalties not paid out yet
+pub const SLASH_EVENTS: Map<u64, SlashEvent> = Map::new("slash_events"); // id: slash found when reconciling
+pub const SLASH_EVENT_SEQ: Item<u64> = Item::new("slash_event_seq"); // id of the next slash event
+pub const POSITION_SLASHES: Map<(&str, u64), Uint128> = Map::new("position_slashes"); // (token_id, slash_event_id): amount lost
+pub const UNAPPLIED_SLASH_WEIGHT: Item<Uint128> = Item::new("unapplied_slash_weight"); // slashed amounts taken out of TOTAL_WEIGHT before the positions are scaled
+pub const VALIDATOR_STRATEGY: Item<ValidatorStrategy> = Item::new("validator_strategy");
+pub const VALIDATOR_CURSOR: Item<u64> = Item::new("validator_cursor"); // next validator for round-robin
+pub const VALIDATOR_TOTALS: Map<&str, Uint128> = Map::new("validator_totals"); // validator_address: amount delegated by all positions
+pub const SHARE_POOL: Item<Position> = Item::new("share_pool"); // position backing the share token, there is no nft for it
+pub const COMPOUND_HISTORY: Map<(&str, u64), CompoundRecord> = Map::new("compound_history"); // (token_id, block_height): compounding
+
+// token_id: position, indexed by the nft owner, by compounding and by lock expiry
+pub fn tokens<'a>() -> IndexedMap<'a, String, Position, PositionIndexes<'a>> {
+    let indexes = PositionIndexes {
+        owner: MultiIndex::new(|_pk, position: &Position| position.owner.clone(), "tokens", "tokens__owner"),
+        compounding: MultiIndex::new(|_pk, position: &Position| u8::from(position.compounding), "tokens", "tokens__compounding"),
+        lock_expiry: MultiIndex::new(
+            |_pk, position: &Position| position.lock.as_ref().map_or(u64::MAX, |lock| lock.expires_at.seconds()),
+            "tokens",
+            "tokens__lock_expiry",
+        ),
+    };
+    IndexedMap::new("tokens", indexes)
+}
+
+pub struct PositionIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, Position, String>,
+    // 1 for compounding positions
+    pub compounding: MultiIndex<'a, u8, Position, String>,
+    // seconds the lock expires at, u64::MAX for positions without a lock
+    pub lock_expiry: MultiIndex<'a, u64, Position, String>,
+}
+
+impl<'a> IndexList<Position> for PositionIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Position>> + '_> {
+        let v: Vec<&dyn Index<Position>> = vec![&self.owner, &self.compounding, &self.lock_expiry];
+        Box::new(v.into_iter())
+    }
+}
+
+#[cw_serde]
+pub struct Position {
+    // owner of the position nft, the contract itself for the share pool
+    pub owner: Addr,
+    // bonded amount, grows with compounding and shrinks with slashes
+    pub principal: Uint128,
+    pub denom: String,
+    // validator operator addresses and the amount bonded to each
+    pub delegations: Vec<PositionDelegation>,
+    pub start_height: u64,
+    pub start_time: Timestamp,
+    // REWARD_INDEX when the position was last settled
+    pub reward_index: Decimal,
+    // reward settled but not paid out yet
+    pub accrued_reward: Uint128,
+    // rewards are re-delegated by Compound
+    pub compounding: bool,
+    // unlocked positions earn rewards on their amount as is
+    pub lock: Option<PositionLock>,
+    // SLASH_EVENT_SEQ when the delegations were last scaled by slash events
+    pub slash_seq: u64,
+}
+
+// operations stopped by the owner or guardian; Claim only pays out matured
+// claims, so it keeps working while everything is paused
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseFlags {
+    // Stake and StakeMore
+    pub stake: bool,
+    // Reward, Unbond, EmergencyUnbond, PartialUnbond and Redeem
+    pub unstake: bool,
+    pub swap: bool,
+}
+
+// lock tier chosen at stake, the multiplier is kept even if the tiers change
+#[cw_serde]
+pub struct PositionLock {
+    pub duration: u64,
+    pub multiplier: Decimal,
+    pub expires_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct LockTier {
+    // seconds
+    pub duration: u64,
+    // applied to the amount to get the position's reward weight
+    pub multiplier: Decimal,
+}
+
+// where early exit penalties go once they are unbonded
+#[cw_serde]
+pub enum PenaltyDestination {
+    // added to the reward index of the remaining positions
+    Stakers {},
+    Treasury {},
+}
+
+#[cw_serde]
+pub struct PendingPenalty {
+    pub amount: Uint128,
+    pub denom: String,
+    pub release_at: Timestamp,
+    pub destination: PenaltyDestination,
+}
+
+#[cw_serde]
+pub struct SlashEvent {
+    pub validator: String,
+    pub height: u64,
+    pub time: Timestamp,
+    // share of the recorded delegation left after the slash
+    pub ratio: Decimal,
+    // lost by all positions together
+    pub amount: Uint128,
+    // REWARD_INDEX when the slash was found, positions earn on the amount
+    // before the slash up to it
+    pub reward_index: Decimal,
+}
+
+#[cw_serde]
+pub struct RewardCheckpoint {
+    pub time: Timestamp,
+    pub index: Decimal,
+}
+
+// fee schedule per operation, fees go to the treasury
+#[cw_serde]
+pub struct FeeConfig {
+    // taken from the funds sent with Stake and StakeMore
+    pub stake: OperationFee,
+    // taken from unbonded coins when they are claimed
+    pub unstake: OperationFee,
+    // taken from the funds sent with Swap
+    pub swap: OperationFee,
+    // taken from rewards when they are paid out or compounded
+    pub performance: OperationFee,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct OperationFee {
+    // basis points of the amount
+    pub bps: u16,
+    // added to the bps part, in the denom of the amount
+    pub flat: Uint128,
+    // bounds of the total fee for some denoms
+    pub limits: Vec<FeeLimit>,
+}
+
+#[cw_serde]
+pub struct FeeLimit {
+    pub denom: String,
+    pub min: Option<Uint128>,
+    pub max: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct Claim {
+    pub amount: Uint128,
+    pub denom: String,
+    pub release_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct PositionDelegation {
+    pub validator: String,
+    pub amount: Uint128,
+}
+
+// how Stake picks the validators to delegate to
+#[cw_serde]
+pub enum ValidatorStrategy {
+    LowestCommission {},
+    RoundRobin {},
+    // lowest commission among the whitelisted validators
+    Whitelist { validators: Vec<String> },
+    // the staker names a validator, which must be whitelisted
+    UserSpecified { whitelist: Vec<String> },
+    // split evenly across the n lowest commission validators
+    TopN { n: u32 },
+    // split across the listed validators in proportion to their weights
+    Weighted { validators: Vec<ValidatorWeight> },
+}
+
+#[cw_serde]
+pub struct ValidatorWeight {
+    pub validator: String,
+    pub weight: u64,
+}
+
+#[cw_serde]
+pub struct CompoundRecord {
+    pub height: u64,
+    pub time: Timestamp,
+    pub reward: Uint128,
+    pub fee: Uint128,
+    // reward minus fee, added to the position amount
+    pub compounded: Uint128,
+}
@@ -0,0 +1,371 @@
+use cosmwasm_std::{DepsMut, StdResult, Uint128, Validator};
+
+use crate::error::ContractError;
+use crate::state::{
+    PositionDelegation, ValidatorStrategy, ValidatorWeight, VALIDATOR_CURSOR, VALIDATOR_STRATEGY,
+};
+
+// reject strategies that could never select a validator
+pub fn validate_strategy(strategy: &ValidatorStrategy) -> Result<(), ContractError> {
+    match strategy {
+        ValidatorStrategy::Whitelist { validators } if validators.is_empty() => Err(
+            ContractError::InvalidStrategy("whitelist must not be empty".into()),
+        ),
+        ValidatorStrategy::UserSpecified { whitelist } if whitelist.is_empty() => Err(
+            ContractError::InvalidStrategy("whitelist must not be empty".into()),
+        ),
+        ValidatorStrategy::TopN { n } if *n == 0 => Err(
+            ContractError::InvalidStrategy("n must be greater than 0".into()),
+        ),
+        ValidatorStrategy::Weighted { validators } => validate_weights(validators),
+        _ => Ok(()),
+    }
+}
+
+fn validate_weights(validators: &[ValidatorWeight]) -> Result<(), ContractError> {
+    if validators.is_empty() {
+        return Err(ContractError::InvalidStrategy("weights must not be empty".into()));
+    }
+    for (i, weighted) in validators.iter().enumerate() {
+        if weighted.weight == 0 {
+            return Err(ContractError::InvalidStrategy(format!(
+                "weight of {} must be greater than 0",
+                weighted.validator,
+            )));
+        }
+        if validators[..i].iter().any(|other| other.validator == weighted.validator) {
+            return Err(ContractError::InvalidStrategy(format!(
+                "{} is listed more than once",
+                weighted.validator,
+            )));
+        }
+    }
+    Ok(())
+}
+
+// pick validators for amount with the configured strategy and split amount
+// between them
+pub fn select_validators(
+    deps: DepsMut,
+    requested: Option<String>,
+    amount: Uint128,
+) -> Result<Vec<PositionDelegation>, ContractError> {
+    let strategy : ValidatorStrategy = VALIDATOR_STRATEGY.load(deps.storage)?;
+    select_with(deps, strategy, requested, amount)
+}
+
+// same as select_validators, but nobody is there to name a validator, so a
+// user-specified whitelist is treated as an admin whitelist
+pub fn select_redelegation_targets(
+    deps: DepsMut,
+    amount: Uint128,
+) -> Result<Vec<PositionDelegation>, ContractError> {
+    let strategy = match VALIDATOR_STRATEGY.load(deps.storage)? {
+        ValidatorStrategy::UserSpecified { whitelist } => ValidatorStrategy::Whitelist { validators: whitelist },
+        strategy => strategy,
+    };
+    select_with(deps, strategy, None, amount)
+}
+
+fn select_with(
+    deps: DepsMut,
+    strategy: ValidatorStrategy,
+    requested: Option<String>,
+    amount: Uint128,
+) -> Result<Vec<PositionDelegation>, ContractError> {
+    // lowest commission first, ties broken by address so the order is stable
+    let mut validators : Vec<Validator> = deps.querier.query_all_validators()?;
+    validators.sort_by(|a, b| {
+        a.commission
+            .cmp(&b.commission)
+            .then_with(|| a.address.cmp(&b.address))
+    });
+    if validators.is_empty() {
+        return Err(ContractError::NoValidators {});
+    }
+
+    // only the user-specified strategy lets the staker choose
+    if let Some(validator) = &requested {
+        if !matches!(strategy, ValidatorStrategy::UserSpecified { .. }) {
+            return Err(ContractError::ValidatorNotAllowed { validator: validator.clone() });
+        }
+    }
+
+    // weighted validators keep their weights, every other strategy splits evenly
+    let selected : Vec<(String, u128)> = match strategy {
+        ValidatorStrategy::LowestCommission {} => vec![(validators[0].address.clone(), 1)],
+        ValidatorStrategy::RoundRobin {} => {
+            let cursor : u64 = VALIDATOR_CURSOR.may_load(deps.storage)?.unwrap_or_default();
+            VALIDATOR_CURSOR.save(deps.storage, &(cursor.wrapping_add(1)))?;
+
+            let mut addresses : Vec<String> = validators.into_iter().map(|v| v.address).collect();
+            addresses.sort();
+            let idx = (cursor % addresses.len() as u64) as usize;
+            vec![(addresses.swap_remove(idx), 1)]
+        }
+        ValidatorStrategy::Whitelist { validators: whitelist } => {
+            let validator = validators
+                .into_iter()
+                .find(|v| whitelist.contains(&v.address))
+                .ok_or(ContractError::NoValidators {})?;
+            vec![(validator.address, 1)]
+        }
+        ValidatorStrategy::UserSpecified { whitelist } => {
+            let requested = requested.ok_or(ContractError::ValidatorRequired {})?;
+            let active = validators.iter().any(|v| v.address == requested);
+            if !active || !whitelist.contains(&requested) {
+                return Err(ContractError::ValidatorNotAllowed { validator: requested });
+            }
+            vec![(requested, 1)]
+        }
+        ValidatorStrategy::TopN { n } => validators
+            .into_iter()
+            .take(n as usize)
+            .map(|v| (v.address, 1))
+            .collect(),
+        ValidatorStrategy::Weighted { validators: weights } => {
+            // inactive validators are skipped, the rest share their weight
+            let selected : Vec<(String, u128)> = weights
+                .into_iter()
+                .filter(|weighted| validators.iter().any(|v| v.address == weighted.validator))
+                .map(|weighted| (weighted.validator, weighted.weight as u128))
+                .collect();
+            if selected.is_empty() {
+                return Err(ContractError::NoValidators {});
+            }
+            selected
+        }
+    };
+
+    Ok(split_by_weight(selected, amount)?)
+}
+
+// shares in proportion to the weights with the rounding remainder going to
+// the first validator; validators that would receive nothing are dropped
+pub fn split_by_weight(validators: Vec<(String, u128)>, amount: Uint128) -> StdResult<Vec<PositionDelegation>> {
+    let total_weight : u128 = validators.iter().map(|(_, weight)| *weight).sum();
+    let mut delegations : Vec<PositionDelegation> = validators
+        .into_iter()
+        .map(|(validator, weight)| PositionDelegation {
+            validator,
+            amount: amount.multiply_ratio(weight, total_weight),
+        })
+        .collect();
+
+    let split : Uint128 = delegations.iter().map(|delegation| delegation.amount).sum();
+    if let Some(first) = delegations.first_mut() {
+        first.amount += amount.checked_sub(split)?;
+    }
+
+    delegations.retain(|delegation| !delegation.amount.is_zero());
+    Ok(delegations)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::{Decimal, Uint128, Validator};
+
+    use super::*;
+
+    const DENOM: &str = "ucony";
+
+    fn validator(address: &str, commission_percent: u64) -> Validator {
+        Validator {
+            address: address.to_string(),
+            commission: Decimal::percent(commission_percent),
+            max_commission: Decimal::percent(100),
+            max_change_rate: Decimal::percent(1),
+        }
+    }
+
+    fn validator_set() -> Vec<Validator> {
+        vec![
+            validator("val_a", 10),
+            validator("val_b", 5),
+            validator("val_c", 20),
+        ]
+    }
+
+    fn delegation(validator: &str, amount: u128) -> PositionDelegation {
+        PositionDelegation {
+            validator: validator.to_string(),
+            amount: Uint128::new(amount),
+        }
+    }
+
+    #[test]
+    fn lowest_commission() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::LowestCommission {})
+            .unwrap();
+
+        let res = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap();
+        assert_eq!(vec![delegation("val_b", 100)], res);
+    }
+
+    #[test]
+    fn no_validators() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &[], &[]);
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::LowestCommission {})
+            .unwrap();
+
+        let err = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::NoValidators {}));
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::RoundRobin {})
+            .unwrap();
+
+        let picked = (0..4)
+            .map(|_| {
+                let res = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap();
+                res[0].validator.clone()
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["val_a", "val_b", "val_c", "val_a"], picked);
+    }
+
+    #[test]
+    fn whitelist() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        let strategy = ValidatorStrategy::Whitelist {
+            validators: vec!["val_a".to_string(), "val_c".to_string()],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+
+        // val_b has the lowest commission but is not whitelisted
+        let res = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap();
+        assert_eq!(vec![delegation("val_a", 100)], res);
+
+        // a whitelist without active validators selects nothing
+        let strategy = ValidatorStrategy::Whitelist {
+            validators: vec!["val_x".to_string()],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+        let err = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::NoValidators {}));
+    }
+
+    #[test]
+    fn user_specified() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        let strategy = ValidatorStrategy::UserSpecified {
+            whitelist: vec!["val_a".to_string(), "val_x".to_string()],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+
+        let res = select_validators(deps.as_mut(), Some("val_a".into()), Uint128::new(100)).unwrap();
+        assert_eq!(vec![delegation("val_a", 100)], res);
+
+        // active but not whitelisted
+        let err = select_validators(deps.as_mut(), Some("val_b".into()), Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::ValidatorNotAllowed { validator } if validator == "val_b"));
+
+        // whitelisted but not in the active set
+        let err = select_validators(deps.as_mut(), Some("val_x".into()), Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::ValidatorNotAllowed { validator } if validator == "val_x"));
+
+        let err = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::ValidatorRequired {}));
+    }
+
+    #[test]
+    fn requested_validator_needs_user_specified() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::LowestCommission {})
+            .unwrap();
+
+        let err = select_validators(deps.as_mut(), Some("val_a".into()), Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::ValidatorNotAllowed { .. }));
+    }
+
+    #[test]
+    fn top_n() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::TopN { n: 2 })
+            .unwrap();
+
+        let res = select_validators(deps.as_mut(), None, Uint128::new(101)).unwrap();
+        assert_eq!(vec![delegation("val_b", 51), delegation("val_a", 50)], res);
+
+        // n larger than the set uses every validator, empty shares are dropped
+        VALIDATOR_STRATEGY
+            .save(deps.as_mut().storage, &ValidatorStrategy::TopN { n: 5 })
+            .unwrap();
+        let res = select_validators(deps.as_mut(), None, Uint128::new(2)).unwrap();
+        assert_eq!(vec![delegation("val_b", 2)], res);
+    }
+
+    #[test]
+    fn weighted() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        let weight = |validator: &str, weight: u64| ValidatorWeight {
+            validator: validator.to_string(),
+            weight,
+        };
+        let strategy = ValidatorStrategy::Weighted {
+            validators: vec![weight("val_a", 1), weight("val_c", 3), weight("val_x", 4)],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+
+        // val_x is not active, so its weight is not counted
+        let res = select_validators(deps.as_mut(), None, Uint128::new(102)).unwrap();
+        assert_eq!(vec![delegation("val_a", 26), delegation("val_c", 76)], res);
+
+        let strategy = ValidatorStrategy::Weighted {
+            validators: vec![weight("val_x", 1)],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+        let err = select_validators(deps.as_mut(), None, Uint128::new(100)).unwrap_err();
+        assert!(matches!(err, ContractError::NoValidators {}));
+    }
+
+    #[test]
+    fn redelegation_targets() {
+        let mut deps = mock_dependencies();
+        deps.querier.update_staking(DENOM, &validator_set(), &[]);
+        let strategy = ValidatorStrategy::UserSpecified {
+            whitelist: vec!["val_c".to_string()],
+        };
+        VALIDATOR_STRATEGY.save(deps.as_mut().storage, &strategy).unwrap();
+
+        let res = select_redelegation_targets(deps.as_mut(), Uint128::new(100)).unwrap();
+        assert_eq!(vec![delegation("val_c", 100)], res);
+    }
+
+    #[test]
+    fn invalid_strategies() {
+        assert!(validate_strategy(&ValidatorStrategy::TopN { n: 0 }).is_err());
+        assert!(validate_strategy(&ValidatorStrategy::Whitelist { validators: vec![] }).is_err());
+        assert!(validate_strategy(&ValidatorStrategy::UserSpecified { whitelist: vec![] }).is_err());
+        assert!(validate_strategy(&ValidatorStrategy::RoundRobin {}).is_ok());
+
+        let weight = |validator: &str, weight: u64| ValidatorWeight {
+            validator: validator.to_string(),
+            weight,
+        };
+        let zero_weight = ValidatorStrategy::Weighted { validators: vec![weight("val_a", 0)] };
+        assert!(validate_strategy(&zero_weight).is_err());
+        let duplicated = ValidatorStrategy::Weighted {
+            validators: vec![weight("val_a", 1), weight("val_a", 2)],
+        };
+        assert!(validate_strategy(&duplicated).is_err());
+    }
+}